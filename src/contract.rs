@@ -2,11 +2,29 @@ use crate::error::ContractError;
 use crate::handlers::{
     execute_extend_subdomain_expiry, execute_register, execute_renew_registration,
     execute_set_subdomain, execute_update_config, execute_update_resolver, execute_withdraw_fees,
-    execute_user_metadata_update,execute_remove_subdomain
+    execute_user_metadata_update,execute_remove_subdomain, execute_set_text,
+    execute_set_chain_address, execute_set_resolver_contract, execute_set_wildcard,
+    execute_set_registrar, execute_claim_subdomain, execute_set_renewal_terms,
+    execute_renew_subdomain, execute_prune_subdomains, execute_set_resolver_only_subdomain,
+    execute_burn_fuses, execute_approve_manager, execute_revoke_manager, execute_transfer_name,
+    execute_list_name, execute_cancel_listing, execute_buy_name, execute_make_offer,
+    execute_accept_offer, execute_reject_offer, execute_withdraw_offer, execute_lease_name,
+    execute_end_lease, execute_set_tld,
 };
+use crate::migrations::{migrate_legacy_names, parse_version, run_migrations};
 use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, MigrateMsg};
-use crate::read_utils::{format_name, query_resolver, query_resolver_address, query_resolver_expiration};
-use crate::state::{config, config_read, Config};
+use crate::read_utils::{
+    format_name, normalize_name, query_all_texts, query_available, query_available_batch,
+    query_fuses, query_lease,
+    query_list_subdomains, query_listing,
+    query_listings, query_managers, query_names_by_owner, query_offers_by_bidder,
+    query_offers_by_name,
+    query_registrar, query_renewal_terms, query_resolver, query_resolver_address,
+    query_resolve_for_chain, query_resolver_expiration, query_text, query_tlds, tld_names,
+};
+use crate::state::{
+    has_legacy_records, Config, Wildcard, CONFIG,
+};
 
 use archid_token::Metadata;
 
@@ -21,6 +39,8 @@ pub type NameExtension = Option<Metadata>;
 
 const CONTRACT_NAME: &str = "crates.io:archid-registry";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+const DEFAULT_MIGRATION_LIMIT: u32 = 100;
+const MAX_MIGRATION_LIMIT: u32 = 500;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -37,8 +57,14 @@ pub fn instantiate(
         cw721: msg.cw721,
         base_cost: msg.base_cost,
         base_expiration: msg.base_expiration,
+        protocol_fee: msg.protocol_fee,
+        royalty: msg.royalty,
+        sync_subdomain_metadata: msg.sync_subdomain_metadata,
+        transfer_policy: msg.transfer_policy,
+        validation: msg.validation,
     };
-    config(deps.storage).save(&config_state)?;
+    config_state.validate()?;
+    CONFIG.save(deps.storage, &config_state)?;
     Ok(Response::default())
 }
 
@@ -49,13 +75,17 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    // names can be fully qualified, bare names fall under `.arch`
+    let tlds = tld_names(deps.storage)?;
     match msg {
-        ExecuteMsg::Register { name } => execute_register(deps, env, info, format_name(name)),
+        ExecuteMsg::Register { name } => {
+            execute_register(deps, env, info, format_name(&tlds, name))
+        }
         ExecuteMsg::RenewRegistration { name } => {
-            execute_renew_registration(deps, env, info, format_name(name))
+            execute_renew_registration(deps, env, info, format_name(&tlds, name))
         }
         ExecuteMsg::UpdateResolver { name, new_resolver } => {
-            execute_update_resolver(info, deps, env, format_name(name), new_resolver)
+            execute_update_resolver(info, deps, env, format_name(&tlds, name), new_resolver)
         }
         ExecuteMsg::RegisterSubdomain {
             domain,
@@ -67,8 +97,8 @@ pub fn execute(
             info,
             deps,
             env,
-            format_name(domain),
-            subdomain,
+            format_name(&tlds, domain),
+            normalize_name(&subdomain),
             new_resolver,
             new_owner,
             expiration,
@@ -81,31 +111,229 @@ pub fn execute(
             info,
             deps,
             env,
-            format_name(domain),
-            subdomain,
+            format_name(&tlds, domain),
+            normalize_name(&subdomain),
             expiration,
         ),
         ExecuteMsg::UpdateUserDomainData {
             name,
             metadata_update,
-        } => execute_user_metadata_update(info, deps, format_name(name), metadata_update),
+        } => execute_user_metadata_update(
+            info,
+            deps,
+            env,
+            format_name(&tlds, name),
+            metadata_update,
+        ),
 
         ExecuteMsg::UpdateConfig { config } => execute_update_config(deps, env, info, config),
 
-        ExecuteMsg::Withdraw { amount } => execute_withdraw_fees(info, deps, amount),
+        ExecuteMsg::Withdraw { amount } => execute_withdraw_fees(info, deps, env, amount),
 
         ExecuteMsg::RemoveSubdomain { domain, subdomain } => {
-            execute_remove_subdomain(info, deps, env, format_name(domain), subdomain)
+            let subdomain = normalize_name(&subdomain);
+            execute_remove_subdomain(info, deps, env, format_name(&tlds, domain), subdomain)
+        }
+        ExecuteMsg::SetText { name, key, value } => {
+            execute_set_text(info, deps, env, format_name(&tlds, name), key, value)
+        }
+        ExecuteMsg::SetChainAddress {
+            name,
+            chain,
+            address,
+        } => execute_set_chain_address(info, deps, env, format_name(&tlds, name), chain, address),
+        ExecuteMsg::SetResolverContract { name, contract } => {
+            execute_set_resolver_contract(info, deps, env, format_name(&tlds, name), contract)
+        }
+        ExecuteMsg::SetWildcard {
+            domain,
+            resolver,
+            resolver_contract,
+        } => {
+            let wildcard = Wildcard {
+                resolver,
+                resolver_contract,
+            };
+            execute_set_wildcard(info, deps, env, format_name(&tlds, domain), Some(wildcard))
+        }
+        ExecuteMsg::RemoveWildcard { domain } => {
+            execute_set_wildcard(info, deps, env, format_name(&tlds, domain), None)
+        }
+        ExecuteMsg::OpenRegistrar { domain, registrar } => {
+            execute_set_registrar(info, deps, env, format_name(&tlds, domain), Some(registrar))
+        }
+        ExecuteMsg::CloseRegistrar { domain } => {
+            execute_set_registrar(info, deps, env, format_name(&tlds, domain), None)
+        }
+        ExecuteMsg::ClaimSubdomain {
+            domain,
+            subdomain,
+            new_resolver,
+        } => execute_claim_subdomain(
+            info,
+            deps,
+            env,
+            format_name(&tlds, domain),
+            normalize_name(&subdomain),
+            new_resolver,
+        ),
+        ExecuteMsg::SetRenewalTerms { domain, terms } => {
+            execute_set_renewal_terms(info, deps, env, format_name(&tlds, domain), Some(terms))
+        }
+        ExecuteMsg::RemoveRenewalTerms { domain } => {
+            execute_set_renewal_terms(info, deps, env, format_name(&tlds, domain), None)
+        }
+        ExecuteMsg::RenewSubdomain { domain, subdomain } => {
+            let subdomain = normalize_name(&subdomain);
+            execute_renew_subdomain(info, deps, env, format_name(&tlds, domain), subdomain)
+        }
+        ExecuteMsg::PruneSubdomains {
+            domain,
+            start_after,
+            limit,
+        } => execute_prune_subdomains(deps, format_name(&tlds, domain), start_after, limit),
+        ExecuteMsg::ApproveManager {
+            name,
+            manager,
+            scopes,
+            expires,
+        } => execute_approve_manager(
+            info,
+            deps,
+            env,
+            format_name(&tlds, name),
+            manager,
+            scopes,
+            expires,
+        ),
+        ExecuteMsg::RevokeManager { name, manager } => {
+            execute_revoke_manager(info, deps, format_name(&tlds, name), manager)
         }
+        ExecuteMsg::TransferName {
+            name,
+            recipient,
+            new_resolver,
+        } => execute_transfer_name(
+            info,
+            deps,
+            env,
+            format_name(&tlds, name),
+            recipient,
+            new_resolver,
+        ),
+        ExecuteMsg::ListName {
+            name,
+            price,
+            denom,
+            expires,
+        } => execute_list_name(info, deps, env, format_name(&tlds, name), price, denom, expires),
+        ExecuteMsg::CancelListing { name } => {
+            execute_cancel_listing(info, deps, format_name(&tlds, name))
+        }
+        ExecuteMsg::BuyName { name, new_resolver } => {
+            execute_buy_name(info, deps, env, format_name(&tlds, name), new_resolver)
+        }
+        ExecuteMsg::MakeOffer { name, expires } => {
+            execute_make_offer(info, deps, env, format_name(&tlds, name), expires)
+        }
+        ExecuteMsg::AcceptOffer { name, bidder } => {
+            execute_accept_offer(info, deps, env, format_name(&tlds, name), bidder)
+        }
+        ExecuteMsg::RejectOffer { name, bidder } => {
+            execute_reject_offer(info, deps, format_name(&tlds, name), bidder)
+        }
+        ExecuteMsg::WithdrawOffer { name } => {
+            execute_withdraw_offer(info, deps, env, format_name(&tlds, name))
+        }
+        ExecuteMsg::LeaseName {
+            name,
+            tenant,
+            expires,
+        } => execute_lease_name(info, deps, env, format_name(&tlds, name), tenant, expires),
+        ExecuteMsg::EndLease { name } => {
+            execute_end_lease(info, deps, env, format_name(&tlds, name))
+        }
+        ExecuteMsg::SetResolverOnlySubdomain {
+            domain,
+            subdomain,
+            resolver,
+            expiration,
+        } => execute_set_resolver_only_subdomain(
+            info,
+            deps,
+            env,
+            format_name(&tlds, domain),
+            normalize_name(&subdomain),
+            resolver,
+            expiration,
+        ),
+        ExecuteMsg::BurnFuses {
+            domain,
+            subdomain,
+            fuses,
+        } => {
+            let subdomain = normalize_name(&subdomain);
+            execute_burn_fuses(info, deps, env, format_name(&tlds, domain), subdomain, fuses)
+        }
+        ExecuteMsg::SetTld { tld, settings } => execute_set_tld(info, deps, tld, settings),
     }
 }
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-        QueryMsg::ResolveRecord { name } => query_resolver(deps, env, name),
-        QueryMsg::ResolveAddress { address } => query_resolver_address(deps, env, address),
-        QueryMsg::RecordExpiration { name } => query_resolver_expiration(deps, env, name),
-        QueryMsg::Config {} => to_binary(&config_read(deps.storage).load()?),
+        QueryMsg::ResolveRecord { name } => query_resolver(deps, env, normalize_name(&name)),
+        QueryMsg::ResolveAddress {
+            address,
+            start_after,
+            limit,
+        } => query_resolver_address(deps, env, address, start_after, limit),
+        QueryMsg::RecordExpiration { name } => {
+            query_resolver_expiration(deps, env, normalize_name(&name))
+        }
+        QueryMsg::NamesByOwner {
+            owner,
+            start_after,
+            limit,
+            tld,
+        } => query_names_by_owner(deps, env, owner, start_after, limit, tld),
+        QueryMsg::Text { name, key } => query_text(deps, env, normalize_name(&name), key),
+        QueryMsg::AllTexts {
+            name,
+            start_after,
+            limit,
+        } => query_all_texts(deps, env, normalize_name(&name), start_after, limit),
+        QueryMsg::ResolveForChain { name, chain } => {
+            query_resolve_for_chain(deps, env, normalize_name(&name), chain)
+        }
+        QueryMsg::Registrar { domain } => query_registrar(deps, env, normalize_name(&domain)),
+        QueryMsg::RenewalTerms { domain } => {
+            query_renewal_terms(deps, env, normalize_name(&domain))
+        }
+        QueryMsg::Fuses { name } => query_fuses(deps, env, normalize_name(&name)),
+        QueryMsg::ListSubdomains {
+            domain,
+            start_after,
+            limit,
+        } => query_list_subdomains(deps, env, normalize_name(&domain), start_after, limit),
+        QueryMsg::Managers { name } => query_managers(deps, env, normalize_name(&name)),
+        QueryMsg::Listing { name } => query_listing(deps, env, normalize_name(&name)),
+        QueryMsg::Listings { start_after, limit } => query_listings(deps, env, start_after, limit),
+        QueryMsg::Lease { name } => query_lease(deps, env, normalize_name(&name)),
+        QueryMsg::OffersByName {
+            name,
+            start_after,
+            limit,
+        } => query_offers_by_name(deps, env, normalize_name(&name), start_after, limit),
+        QueryMsg::OffersByBidder {
+            bidder,
+            start_after,
+            limit,
+        } => query_offers_by_bidder(deps, env, bidder, start_after, limit),
+        QueryMsg::Config {} => to_binary(&CONFIG.load(deps.storage)?),
+        QueryMsg::ValidationRules {} => to_binary(&CONFIG.load(deps.storage)?.validation),
+        QueryMsg::Tlds {} => query_tlds(deps, env),
+        QueryMsg::Available { name } => query_available(deps, env, name),
+        QueryMsg::AvailableBatch { names } => query_available_batch(deps, env, names),
     }
 }
 
@@ -118,16 +346,32 @@ pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, Contract
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+pub fn migrate(mut deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
     let original_version = get_contract_version(deps.storage)?;
-    let name = CONTRACT_NAME.to_string();
-    let version = CONTRACT_VERSION.to_string();
-    if original_version.contract != name {
+    if original_version.contract != CONTRACT_NAME {
         return Err(ContractError::InvalidInput {});
     }
-    if original_version.version >= version {
+    let from = parse_version(&original_version.version)?;
+    let to = parse_version(CONTRACT_VERSION)?;
+    // migrating again to the same version resumes an unfinished record transfer
+    let resuming = from == to && has_legacy_records(deps.storage);
+    if from >= to && !resuming {
         return Err(ContractError::InvalidInput {});
     }
+    let applied = run_migrations(deps.storage, &env, &msg, &from, &to)?;
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    Ok(Response::default())
-}
\ No newline at end of file
+    let limit = msg
+        .limit
+        .unwrap_or(DEFAULT_MIGRATION_LIMIT)
+        .min(MAX_MIGRATION_LIMIT) as usize;
+    let migrated = migrate_legacy_names(deps.branch(), limit)?;
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("migrated_records", migrated.to_string())
+        .add_attribute(
+            "migration_complete",
+            (!has_legacy_records(deps.storage)).to_string(),
+        )
+        .add_attribute("from_version", from.to_string())
+        .add_attribute("steps", applied.join(",")))
+}
@@ -30,4 +30,53 @@ pub enum ContractError {
     NameOwnershipExpired { name: String },
     #[error("Invalid character(char {c}")]
     InvalidCharacter { c: char },
+
+    #[error("Name starts or ends with a hyphen (name {name})")]
+    InvalidHyphen { name: String },
+
+    #[error("Name is reserved (name {name})")]
+    ReservedName { name: String },
+
+    #[error("Invalid internationalised name (name {name})")]
+    InvalidIdn { name: String },
+
+    #[error("Name mixes scripts (name {name})")]
+    MixedScriptName { name: String },
+
+    #[error("Name is confusable with an ascii name (name {name})")]
+    ConfusableName { name: String },
+
+    #[error("Text record too long (length {length} max_length {max_length})")]
+    TextRecordTooLong { length: u64, max_length: u64 },
+    #[error("Too many text records (max {max})")]
+    TooManyTextRecords { max: u64 },
+    #[error("Too many chain addresses (max {max})")]
+    TooManyChainAddresses { max: u64 },
+
+    #[error("Invalid chain identifier (chain {chain})")]
+    InvalidChain { chain: String },
+    #[error("Invalid address for chain (chain {chain} address {address})")]
+    InvalidChainAddress { chain: String, address: String },
+
+    #[error("Subdomains of this domain are not open for registration (domain {domain})")]
+    RegistrarClosed { domain: String },
+    #[error("Subdomains of this domain can't be renewed by their holders (domain {domain})")]
+    RenewalClosed { domain: String },
+
+    #[error("Blocked by a burned fuse (fuse {fuse})")]
+    FuseBurned { fuse: String },
+
+    #[error("Name is not for sale (name {name})")]
+    NotListed { name: String },
+    #[error("No open offer (name {name} bidder {bidder})")]
+    OfferNotFound { name: String, bidder: String },
+    #[error("Name is leased until the lease ends (name {name})")]
+    NameLeased { name: String },
+    #[error("Not enough fees to withdraw (available {available})")]
+    InsufficientFees { available: Uint128 },
+
+    #[error("Only unordered IBC channels are supported")]
+    IbcOrderedChannel {},
+    #[error("Invalid IBC channel version (version {version}, expected {expected})")]
+    IbcInvalidVersion { version: String, expected: String },
 }
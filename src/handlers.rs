@@ -2,21 +2,40 @@ use crate::error::ContractError;
 
 use crate::msg::MetaDataUpdateMsg;
 use crate::read_utils::{
-    get_subdomain_prefix, is_expired, query_current_metadata, query_name_owner, validate_name,
-    validate_subdomain,
+    active_listing, ancestors_expiration, ancestors_expired, get_subdomain_prefix, is_authorized,
+    is_expired, is_stale, name_config, query_current_metadata, query_name_owner,
+    validate_chain_address, validate_name, validate_subdomain, DEFAULT_TLD,
+};
+use crate::state::{
+    active_lease, chain_addresses, clear_name_settings, has_fuse, may_load_record, next_epoch,
+    prune_managers, remove_offer, remove_record, save_offer, save_record, settle_lease,
+    text_records, Config, Fuse, Lease, Listing, Manager, ManagerScope, NameRecord, Offer,
+    Registrar, RenewalTerms, SubDomainStatus, Tld, Wildcard, CONFIG, ESCROW, FUSES, LEASES,
+    LISTINGS, MANAGERS, OFFERS, REGISTRARS, RENEWAL_TERMS, RESOLVER_OWNERS, SUBDOMAINS, TLDS,
+    WILDCARDS,
 };
-use crate::state::{config, config_read, resolver, Config, NameRecord, SubDomainStatus};
 use crate::write_utils::{
-    burn_handler, burn_remint_subdomain, mint_handler, register_new_subdomain,
-    remove_subdomain_metadata, send_data_update, send_tokens, update_metadata_expiry,
-    update_subdomain_expiry, update_subdomain_metadata, DENOM,
+    burn_handler, burn_remint_subdomain, hand_over_name, mint_handler,
+    mint_resolver_only_subdomain, register_new_subdomain, remove_names, remove_subdomain_metadata,
+    send_coin, send_data_update, send_tokens, set_resolver_only_subdomain, split_payment,
+    subdomain_descendants, update_metadata_expiry, update_subdomain_expiry,
+    update_subdomain_metadata, DENOM,
 };
 use archid_token::Metadata;
 
-use cosmwasm_std::{Addr, DepsMut, Env, MessageInfo, Response, Uint128};
-use cw_utils::must_pay;
+use cosmwasm_std::{
+    Addr, CosmosMsg, DepsMut, Env, MessageInfo, Order, Response, StdResult, Uint128,
+};
+use cw_storage_plus::Bound;
+use cw_utils::{must_pay, nonpayable, one_coin};
 use std::convert::TryFrom;
 const MAX_BASE_INTERVAL: u64 = 3;
+const MAX_TEXT_KEY_LENGTH: u64 = 64;
+const MAX_TEXT_VALUE_LENGTH: u64 = 1024;
+const MAX_TEXT_RECORDS: u64 = 32;
+const MAX_CHAIN_ADDRESSES: u64 = 32;
+const DEFAULT_PRUNE_LIMIT: u32 = 10;
+const MAX_PRUNE_LIMIT: u32 = 30;
 
 pub fn execute_register(
     deps: DepsMut,
@@ -24,10 +43,9 @@ pub fn execute_register(
     info: MessageInfo,
     name: String,
 ) -> Result<Response, ContractError> {
-    validate_name(&name)?;
-    let key = &name.as_bytes();
-    let curr = resolver(deps.storage).may_load(key)?;
-    let c: Config = config_read(deps.storage).load()?;
+    validate_name(deps.storage, &name)?;
+    let curr = may_load_record(deps.storage, &name)?;
+    let c: Config = name_config(deps.storage, &name)?;
     let res = must_pay(&info, &String::from(DENOM))?;
     let mut messages = Vec::new();
     let mut registration: u64 =
@@ -45,6 +63,9 @@ pub fn execute_register(
         } else {
             let burn_msg = burn_handler(&name, &c.cw721)?;
             messages.push(burn_msg);
+            clear_name_settings(deps.storage, &name)?;
+            // subdomains minted under the previous registration become stale
+            next_epoch(deps.storage, &name)?;
         }
     }
     let expiration =
@@ -55,10 +76,14 @@ pub fn execute_register(
         resolver: info.sender.clone(),
         created,
         expiration,
+        resolver_contract: None,
+        parent_epoch: 0,
+        resolver_only: false,
     };
     let mint_resp = mint_handler(&name, &info.sender, &c.cw721, created, expiration)?;
     messages.push(mint_resp);
-    resolver(deps.storage).save(key, &record)?;
+    save_record(deps.storage, &name, &record)?;
+    RESOLVER_OWNERS.save(deps.storage, &name, &info.sender)?;
     Ok(Response::new()
         .add_messages(messages)
         .add_attribute("action", "register")
@@ -71,34 +96,38 @@ pub fn execute_renew_registration(
     info: MessageInfo,
     name: String,
 ) -> Result<Response, ContractError> {
-    validate_name(&name)?;
-    let key = &name.as_bytes();
-    if (resolver(deps.storage).may_load(key)?).is_none() {
+    if (may_load_record(deps.storage, &name)?).is_none() {
         return Err(ContractError::InvalidInput {});
     }
-    let curr = (resolver(deps.storage).may_load(key)?).unwrap();
+    settle_lease(deps.storage, &env.block, &name)?;
+    let curr = (may_load_record(deps.storage, &name)?).unwrap();
 
-    let c: Config = config_read(deps.storage).load()?;
-    if is_expired(&deps, key, &env.block) {
+    let c: Config = name_config(deps.storage, &name)?;
+    if is_expired(&deps, &name, &env.block) {
         return Err(ContractError::NameOwnershipExpired { name });
     }
-    let owner_response = query_name_owner(&name, &c.cw721, &deps).unwrap();
+    let owner_response = query_name_owner(&name, &c.cw721, &deps)?;
 
     if owner_response.owner != info.sender {
         return Err(ContractError::Unauthorized {});
     }
 
+    // renewing only extends the registration, the resolver and its
+    // RESOLVER_OWNERS snapshot are left as they are
     let record = NameRecord {
-        resolver: info.sender.clone(),
+        resolver: curr.resolver.clone(),
         created: env.block.time.seconds(),
         expiration: c.base_expiration + curr.expiration,
+        resolver_contract: curr.resolver_contract.clone(),
+        parent_epoch: curr.parent_epoch,
+        resolver_only: curr.resolver_only,
     };
 
     let res = must_pay(&info, &String::from(DENOM))?;
     if res != c.base_cost {
         return Err(ContractError::InvalidPayment { amount: res });
     }
-    resolver(deps.storage).save(key, &record)?;
+    save_record(deps.storage, &name, &record)?;
     let resp = update_metadata_expiry(
         deps,
         &c.cw721,
@@ -123,7 +152,7 @@ expiration for a subdomain can be extend by domain owner using
 #[allow(clippy::too_many_arguments)]
 pub fn execute_set_subdomain(
     info: MessageInfo,
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     domain: String,
     subdomain: String,
@@ -131,52 +160,60 @@ pub fn execute_set_subdomain(
     new_owner: Addr,
     expiration: u64,
 ) -> Result<Response, ContractError> {
+    // only the new label is validated, the parent exists and can itself be a subdomain
+    validate_subdomain(deps.storage, &domain, &subdomain)?;
     //
-    validate_name(&domain)?;
-    //
-    validate_subdomain(&subdomain)?;
-    //
-    let c: Config = config_read(deps.storage).load()?;
+    let c: Config = name_config(deps.storage, &domain)?;
     //
     let domain_route: String = format!("{}.{}", subdomain, domain);
-    //
-    let key = domain_route.as_bytes();
     // Check if a domain nft is currently in existence
 
     // check if doman resolves to a NameRecord throw error otherwise
-    if resolver(deps.storage)
-        .may_load(domain.as_bytes())?
-        .is_none()
-    {
+    if may_load_record(deps.storage, &domain)?.is_none() {
         return Err(ContractError::InvalidInput {});
     }
     // load domain Name Record
-    let domain_config: NameRecord = (resolver(deps.storage).may_load(domain.as_bytes())?).unwrap();
+    check_minted(&deps, &domain)?;
+    let domain_config: NameRecord = (may_load_record(deps.storage, &domain)?).unwrap();
 
     // get the current name owner
-    let owner_response = query_name_owner(&domain, &c.cw721, &deps).unwrap();
+    let owner_response = query_name_owner(&domain, &c.cw721, &deps)?;
 
-    if domain_config.is_expired(&env.block) {
+    if domain_config.is_expired(&env.block)
+        || ancestors_expired(deps.storage, &domain_route, &env.block)?
+    {
         return Err(ContractError::NameOwnershipExpired { name: domain });
     }
-    if owner_response.owner != info.sender {
-        return Err(ContractError::Unauthorized {});
-    }
-    //set expiration to domain expiration if subdomain configuration
-    let _expiration = match expiration > domain_config.expiration {
-        true => &domain_config.expiration,
+    check_authorized(
+        &mut deps,
+        &env,
+        &domain,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Subdomains,
+    )?;
+    //set expiration to the earliest ancestor expiration if subdomain configuration exceeds it
+    let max_expiration =
+        ancestors_expiration(deps.storage, &domain_route)?.unwrap_or(domain_config.expiration);
+    let _expiration = match expiration > max_expiration {
+        true => &max_expiration,
         false => &expiration,
     };
 
     let subdomain_status: SubDomainStatus;
     // add subdomain metadata to top level domain but only if hasnt been registerd
-    if resolver(deps.storage).may_load(key).unwrap().is_none() {
-        subdomain_status = SubDomainStatus::NewSubdomain;
-    } else {
-        match is_expired(&deps, key, &env.block) {
+    match may_load_record(deps.storage, &domain_route)? {
+        None => subdomain_status = SubDomainStatus::NewSubdomain,
+        Some(record) => match is_expired(&deps, &domain_route, &env.block) {
             true => subdomain_status = SubDomainStatus::ExistingMintExpired,
+            false if record.resolver_only => {
+                if new_resolver != record.resolver {
+                    check_fuse(&deps, &env, &domain_route, Fuse::CannotChangeResolver)?;
+                }
+                subdomain_status = SubDomainStatus::ExistingResolverOnly
+            }
             false => subdomain_status = SubDomainStatus::ExistingMintActive,
-        }
+        },
     }
 
     let messages = match subdomain_status {
@@ -200,6 +237,14 @@ pub fn execute_set_subdomain(
             new_owner,
             *_expiration,
         ),
+        SubDomainStatus::ExistingResolverOnly => mint_resolver_only_subdomain(
+            deps,
+            c.cw721,
+            domain.clone(),
+            subdomain.clone(),
+            new_resolver,
+            new_owner,
+        ),
         SubDomainStatus::ExistingMintActive => return Err(ContractError::Unauthorized {}),
     };
     Ok(Response::new()
@@ -211,17 +256,13 @@ pub fn execute_set_subdomain(
 
 pub fn execute_extend_subdomain_expiry(
     info: MessageInfo,
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     domain: String,
     subdomain: String,
     expiration: u64,
 ) -> Result<Response, ContractError> {
-    validate_name(&domain)?;
-    //
-    validate_subdomain(&subdomain)?;
-    //
-    let c: Config = config_read(deps.storage).load()?;
+    let c: Config = name_config(deps.storage, &domain)?;
     //
     let domain_route: String = format!("{}.{}", subdomain, domain);
 
@@ -229,34 +270,44 @@ pub fn execute_extend_subdomain_expiry(
 
     // check if doman resolves to a NameRecord throw error otherwise
 
-    if resolver(deps.storage)
-        .may_load(domain_route.as_bytes())?
-        .is_none()
-    {
+    if may_load_record(deps.storage, &domain_route)?.is_none() {
         return Err(ContractError::InvalidInput {});
     }
     // load domain Name Record
-    let domain_config: NameRecord = (resolver(deps.storage).may_load(domain.as_bytes())?).unwrap();
-    let subdomain_config: NameRecord =
-        (resolver(deps.storage).may_load(domain_route.as_bytes())?).unwrap();
+    check_minted(&deps, &domain)?;
+    let domain_config: NameRecord = (may_load_record(deps.storage, &domain)?).unwrap();
+    let subdomain_config: NameRecord = (may_load_record(deps.storage, &domain_route)?).unwrap();
     // get the current name owner
-    let owner_response = query_name_owner(&domain, &c.cw721, &deps).unwrap();
+    let owner_response = query_name_owner(&domain, &c.cw721, &deps)?;
 
-    if domain_config.is_expired(&env.block) {
+    if domain_config.is_expired(&env.block)
+        || ancestors_expired(deps.storage, &domain_route, &env.block)?
+    {
         return Err(ContractError::NameOwnershipExpired { name: domain });
     }
-    if owner_response.owner != info.sender {
-        return Err(ContractError::Unauthorized {});
-    }
+    check_authorized(
+        &mut deps,
+        &env,
+        &domain,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Subdomains,
+    )?;
     //println!("{:?}",subdomain_config.expiration);
     //println!("{:?}",expiration);
     if expiration <= subdomain_config.expiration {
         return Err(ContractError::InvalidInput {});
     }
-    let _expiration = match expiration > domain_config.expiration {
-        true => domain_config.expiration,
+    let max_expiration =
+        ancestors_expiration(deps.storage, &domain_route)?.unwrap_or(domain_config.expiration);
+    let _expiration = match expiration > max_expiration {
+        true => max_expiration,
         false => expiration,
     };
+    // the cap can fall below the current expiration of older subdomains
+    if _expiration < subdomain_config.expiration {
+        check_fuse(&deps, &env, &domain_route, Fuse::CannotShortenExpiry)?;
+    }
     let messages = update_subdomain_expiry(c.cw721, deps, domain, subdomain, _expiration)?;
 
     Ok(Response::new()
@@ -272,41 +323,49 @@ pub fn execute_update_config(
     info: MessageInfo,
     config_update: Config,
 ) -> Result<Response, ContractError> {
-    let c: Config = config_read(deps.storage).load()?;
+    let c: Config = CONFIG.load(deps.storage)?;
     if c.admin != info.sender {
         return Err(ContractError::Unauthorized {});
     }
+    config_update.validate()?;
 
-    config(deps.storage).save(&config_update)?;
+    CONFIG.save(deps.storage, &config_update)?;
     Ok(Response::default())
 }
 pub fn execute_update_resolver(
     info: MessageInfo,
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     name: String,
     new_resolver: Addr,
 ) -> Result<Response, ContractError> {
-    let c: Config = config_read(deps.storage).load()?;
+    let c: Config = name_config(deps.storage, &name)?;
 
     let owner_response = query_name_owner(&name, &c.cw721, &deps)?;
-    if owner_response.owner != info.sender {
-        return Err(ContractError::Unauthorized {});
-    }
+    check_controller(
+        &mut deps,
+        &env,
+        &name,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Resolver,
+    )?;
+    settle_lease(deps.storage, &env.block, &name)?;
     let subdomain = get_subdomain_prefix(name.clone());
-    let key = name.as_bytes();
-    let curr = (resolver(deps.storage).may_load(key)?).unwrap();
-    if curr.is_expired(&env.block) {
+    let curr = (may_load_record(deps.storage, &name)?).unwrap();
+    if curr.is_expired(&env.block) || is_stale(deps.storage, &name, &curr)? {
         return Err(ContractError::NameOwnershipExpired { name });
     }
-    let key = name.as_bytes();
     let record = NameRecord {
         resolver: new_resolver.clone(),
         created: curr.created,
         expiration: curr.expiration,
+        resolver_contract: curr.resolver_contract.clone(),
+        parent_epoch: curr.parent_epoch,
+        resolver_only: curr.resolver_only,
     };
     let mut messages = Vec::new();
-    if let Some(s) = subdomain {
+    if let Some(s) = subdomain.filter(|_| c.sync_subdomain_metadata) {
         //let s = subdomain.unwrap();
         let resp = update_subdomain_metadata(
             &deps,
@@ -319,7 +378,9 @@ pub fn execute_update_resolver(
 
         messages.push(resp);
     }
-    resolver(deps.storage).save(key, &record)?;
+    save_record(deps.storage, &name, &record)?;
+    let owner = deps.api.addr_validate(&owner_response.owner)?;
+    RESOLVER_OWNERS.save(deps.storage, &name, &owner)?;
     Ok(Response::new()
         .add_messages(messages)
         .add_attribute("action", "update_resolver")
@@ -328,30 +389,48 @@ pub fn execute_update_resolver(
 pub fn execute_withdraw_fees(
     info: MessageInfo,
     deps: DepsMut,
+    env: Env,
     amount: Uint128,
 ) -> Result<Response, ContractError> {
-    let c: Config = config_read(deps.storage).load()?;
+    let c: Config = CONFIG.load(deps.storage)?;
     if c.admin != info.sender {
         return Err(ContractError::Unauthorized {});
     }
+    // funds escrowed for open offers belong to the bidders
+    let balance = deps
+        .querier
+        .query_balance(&env.contract.address, DENOM)?
+        .amount;
+    let escrowed = ESCROW.may_load(deps.storage, DENOM)?.unwrap_or_default();
+    let available = balance.saturating_sub(escrowed);
+    if amount > available {
+        return Err(ContractError::InsufficientFees { available });
+    }
     let resp = send_tokens(&c.wallet, amount)?;
     Ok(Response::new().add_message(resp))
 }
 
 pub fn execute_user_metadata_update(
     info: MessageInfo,
-    deps: DepsMut,
+    mut deps: DepsMut,
+    env: Env,
     name: String,
     update: MetaDataUpdateMsg,
 ) -> Result<Response, ContractError> {
-    let c: Config = config_read(deps.storage).load()?;
+    let c: Config = name_config(deps.storage, &name)?;
     let cw721 = c.cw721;
-    let owner_response = query_name_owner(&name, &cw721, &deps).unwrap();
+    check_minted(&deps, &name)?;
+    let owner_response = query_name_owner(&name, &cw721, &deps)?;
 
-    if owner_response.owner != info.sender {
-        return Err(ContractError::Unauthorized {});
-    }
-    let current_metadata: Metadata = query_current_metadata(&name, &cw721, &deps).unwrap();
+    check_controller(
+        &mut deps,
+        &env,
+        &name,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Metadata,
+    )?;
+    let current_metadata: Metadata = query_current_metadata(&name, &cw721, &deps)?;
     let new_metadata = Metadata {
         description: update.clone().description,
         name: Some(name.clone()),
@@ -371,34 +450,62 @@ pub fn execute_user_metadata_update(
 }
 pub fn execute_remove_subdomain(
     info: MessageInfo,
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     domain: String,
     subdomain: String,
 ) -> Result<Response, ContractError> {
-    let c: Config = config_read(deps.storage).load()?;
+    let c: Config = name_config(deps.storage, &domain)?;
     let domain_route = format!("{}.{}", subdomain, domain);
-    let key = domain_route.as_bytes();
     let mut messages = Vec::new();
 
-    let owner_response = query_name_owner(&domain, &c.cw721, &deps).unwrap();
-    resolver(deps.storage).remove(key);
-    if owner_response.owner != info.sender {
-        return Err(ContractError::Unauthorized {});
+    check_minted(&deps, &domain)?;
+    let owner_response = query_name_owner(&domain, &c.cw721, &deps)?;
+    let resolver_only =
+        may_load_record(deps.storage, &domain_route)?.map_or(false, |record| record.resolver_only);
+    check_fuse(&deps, &env, &domain_route, Fuse::CannotRemove)?;
+    // removing a subdomain takes its descendants along, so it is refused
+    // while any of them is protected from removal
+    for name in subdomain_descendants(deps.storage, &domain_route)?.iter() {
+        check_fuse(&deps, &env, name, Fuse::CannotRemove)?;
+    }
+    remove_record(deps.storage, &domain_route)?;
+    clear_name_settings(deps.storage, &domain_route)?;
+    check_authorized(
+        &mut deps,
+        &env,
+        &domain,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Subdomains,
+    )?;
+    // resolver-only subdomains have no NFT to burn and no descendants
+    if resolver_only {
+        return Ok(Response::new()
+            .add_attribute("action", "remove_subdomain")
+            .add_attribute("domain", domain)
+            .add_attribute("subdomain", subdomain));
     }
-    let subdomain_owner = query_name_owner(&domain_route, &c.cw721, &deps).unwrap();
+    let subdomain_owner = query_name_owner(&domain_route, &c.cw721, &deps)?;
     // if owner of the minted subdomain is not owner of the top level domain
     // and subdomain is not expired
-    if !is_expired(&deps, key, &env.block) && subdomain_owner.owner != info.sender {
+    if !is_expired(&deps, &domain_route, &env.block)
+        && subdomain_owner.owner != owner_response.owner
+    {
         return Err(ContractError::NameTaken { name: domain_route });
     }
-    messages.push(remove_subdomain_metadata(
-        &deps,
-        &c.cw721,
-        domain.clone(),
-        subdomain.clone(),
-    )?);
+    if c.sync_subdomain_metadata {
+        messages.push(remove_subdomain_metadata(
+            &deps,
+            &c.cw721,
+            domain.clone(),
+            subdomain.clone(),
+        )?);
+    }
     messages.push(burn_handler(&domain_route, &c.cw721)?);
+    // names below the removed subdomain go with it
+    let descendants = subdomain_descendants(deps.storage, &domain_route)?;
+    messages.extend(remove_names(deps.storage, &descendants, &c.cw721)?);
 
     Ok(Response::new()
         .add_messages(messages)
@@ -406,3 +513,1105 @@ pub fn execute_remove_subdomain(
         .add_attribute("domain", domain)
         .add_attribute("subdomain", subdomain))
 }
+
+pub fn execute_set_text(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    name: String,
+    key: String,
+    value: String,
+) -> Result<Response, ContractError> {
+    let c: Config = name_config(deps.storage, &name)?;
+    if key.is_empty() {
+        return Err(ContractError::InvalidInput {});
+    }
+    if key.len() as u64 > MAX_TEXT_KEY_LENGTH {
+        return Err(ContractError::TextRecordTooLong {
+            length: key.len() as u64,
+            max_length: MAX_TEXT_KEY_LENGTH,
+        });
+    }
+    if value.len() as u64 > MAX_TEXT_VALUE_LENGTH {
+        return Err(ContractError::TextRecordTooLong {
+            length: value.len() as u64,
+            max_length: MAX_TEXT_VALUE_LENGTH,
+        });
+    }
+    if is_expired(&deps, &name, &env.block) {
+        return Err(ContractError::NameOwnershipExpired { name });
+    }
+    settle_lease(deps.storage, &env.block, &name)?;
+    let owner_response = query_name_owner(&name, &c.cw721, &deps)?;
+    check_controller(
+        &mut deps,
+        &env,
+        &name,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Resolver,
+    )?;
+
+    let texts = text_records(deps.storage, &env.block, &name)?;
+    if value.is_empty() {
+        texts.remove(deps.storage, (name.as_str(), key.as_str()));
+    } else {
+        let exists = texts.has(deps.storage, (name.as_str(), key.as_str()));
+        let count = texts
+            .prefix(name.as_str())
+            .keys_raw(deps.storage, None, None, Order::Ascending)
+            .count() as u64;
+        if !exists && count >= MAX_TEXT_RECORDS {
+            return Err(ContractError::TooManyTextRecords {
+                max: MAX_TEXT_RECORDS,
+            });
+        }
+        texts.save(deps.storage, (name.as_str(), key.as_str()), &value)?;
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_text")
+        .add_attribute("domain", name)
+        .add_attribute("key", key))
+}
+
+pub fn execute_set_chain_address(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    name: String,
+    chain: String,
+    address: String,
+) -> Result<Response, ContractError> {
+    let c: Config = name_config(deps.storage, &name)?;
+    if !address.is_empty() {
+        validate_chain_address(&chain, &address)?;
+    }
+    if is_expired(&deps, &name, &env.block) {
+        return Err(ContractError::NameOwnershipExpired { name });
+    }
+    settle_lease(deps.storage, &env.block, &name)?;
+    let owner_response = query_name_owner(&name, &c.cw721, &deps)?;
+    check_controller(
+        &mut deps,
+        &env,
+        &name,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Resolver,
+    )?;
+
+    let chains = chain_addresses(deps.storage, &env.block, &name)?;
+    if address.is_empty() {
+        chains.remove(deps.storage, (name.as_str(), chain.as_str()));
+    } else {
+        let exists = chains.has(deps.storage, (name.as_str(), chain.as_str()));
+        let count = chains
+            .prefix(name.as_str())
+            .keys_raw(deps.storage, None, None, Order::Ascending)
+            .count() as u64;
+        if !exists && count >= MAX_CHAIN_ADDRESSES {
+            return Err(ContractError::TooManyChainAddresses {
+                max: MAX_CHAIN_ADDRESSES,
+            });
+        }
+        chains.save(deps.storage, (name.as_str(), chain.as_str()), &address)?;
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_chain_address")
+        .add_attribute("domain", name)
+        .add_attribute("chain", chain))
+}
+
+pub fn execute_set_resolver_contract(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    name: String,
+    contract: Option<Addr>,
+) -> Result<Response, ContractError> {
+    let c: Config = name_config(deps.storage, &name)?;
+    let owner_response = query_name_owner(&name, &c.cw721, &deps)?;
+    check_controller(
+        &mut deps,
+        &env,
+        &name,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Resolver,
+    )?;
+    settle_lease(deps.storage, &env.block, &name)?;
+    let mut curr = may_load_record(deps.storage, &name)?
+        .ok_or(ContractError::NameNotExists { name: name.clone() })?;
+    if curr.is_expired(&env.block) || is_stale(deps.storage, &name, &curr)? {
+        return Err(ContractError::NameOwnershipExpired { name });
+    }
+    let resolver_contract = match contract {
+        Some(addr) => Some(deps.api.addr_validate(addr.as_str())?),
+        None => None,
+    };
+    curr.resolver_contract = resolver_contract;
+    save_record(deps.storage, &name, &curr)?;
+    let owner = deps.api.addr_validate(&owner_response.owner)?;
+    RESOLVER_OWNERS.save(deps.storage, &name, &owner)?;
+
+    let mode = match &curr.resolver_contract {
+        Some(addr) => addr.to_string(),
+        None => String::from("registry"),
+    };
+    Ok(Response::new()
+        .add_attribute("action", "set_resolver_contract")
+        .add_attribute("domain", name)
+        .add_attribute("resolver_contract", mode))
+}
+
+pub fn execute_set_wildcard(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    domain: String,
+    wildcard: Option<Wildcard>,
+) -> Result<Response, ContractError> {
+    let c: Config = name_config(deps.storage, &domain)?;
+    if is_expired(&deps, &domain, &env.block) {
+        return Err(ContractError::NameOwnershipExpired { name: domain });
+    }
+    let owner_response = query_name_owner(&domain, &c.cw721, &deps)?;
+    check_authorized(
+        &mut deps,
+        &env,
+        &domain,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Subdomains,
+    )?;
+
+    match wildcard {
+        Some(w) => {
+            let resolver_contract = match w.resolver_contract {
+                Some(addr) => Some(deps.api.addr_validate(addr.as_str())?),
+                None => None,
+            };
+            let w = Wildcard {
+                resolver: deps.api.addr_validate(w.resolver.as_str())?,
+                resolver_contract,
+            };
+            WILDCARDS.save(deps.storage, &domain, &w)?;
+        }
+        None => WILDCARDS.remove(deps.storage, &domain),
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_wildcard")
+        .add_attribute("domain", domain))
+}
+
+pub fn execute_set_registrar(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    domain: String,
+    registrar: Option<Registrar>,
+) -> Result<Response, ContractError> {
+    let c: Config = name_config(deps.storage, &domain)?;
+    if is_expired(&deps, &domain, &env.block) {
+        return Err(ContractError::NameOwnershipExpired { name: domain });
+    }
+    let owner_response = query_name_owner(&domain, &c.cw721, &deps)?;
+    check_authorized(
+        &mut deps,
+        &env,
+        &domain,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Subdomains,
+    )?;
+
+    match registrar {
+        Some(r) => {
+            if r.duration == 0
+                || r.denom.is_empty()
+                || r.min_length == 0
+                || r.min_length > r.max_length
+            {
+                return Err(ContractError::InvalidInput {});
+            }
+            REGISTRARS.save(deps.storage, &domain, &r)?;
+        }
+        None => REGISTRARS.remove(deps.storage, &domain),
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_registrar")
+        .add_attribute("domain", domain))
+}
+
+/// Registers a subdomain to the sender on the terms of the domain's
+/// registrar, the payment is split between the domain owner and the
+/// protocol wallet by `Config::protocol_fee`
+pub fn execute_claim_subdomain(
+    info: MessageInfo,
+    deps: DepsMut,
+    env: Env,
+    domain: String,
+    subdomain: String,
+    new_resolver: Option<Addr>,
+) -> Result<Response, ContractError> {
+    validate_subdomain(deps.storage, &domain, &subdomain)?;
+    let c: Config = name_config(deps.storage, &domain)?;
+    let domain_route: String = format!("{}.{}", subdomain, domain);
+
+    let registrar =
+        REGISTRARS
+            .may_load(deps.storage, &domain)?
+            .ok_or(ContractError::RegistrarClosed {
+                domain: domain.clone(),
+            })?;
+    let length = subdomain.len() as u64;
+    if length < registrar.min_length {
+        return Err(ContractError::NameTooShort {
+            length,
+            min_length: registrar.min_length,
+        });
+    }
+    if length > registrar.max_length {
+        return Err(ContractError::NameTooLong {
+            length,
+            max_length: registrar.max_length,
+        });
+    }
+    if !registrar.permits(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_payment(&info, registrar.price, &registrar.denom)?;
+
+    let domain_config =
+        may_load_record(deps.storage, &domain)?.ok_or(ContractError::NameNotExists {
+            name: domain.clone(),
+        })?;
+    if domain_config.is_expired(&env.block)
+        || ancestors_expired(deps.storage, &domain_route, &env.block)?
+    {
+        return Err(ContractError::NameOwnershipExpired { name: domain });
+    }
+    let subdomain_status = match may_load_record(deps.storage, &domain_route)? {
+        None => SubDomainStatus::NewSubdomain,
+        Some(_) if is_expired(&deps, &domain_route, &env.block) => {
+            SubDomainStatus::ExistingMintExpired
+        }
+        Some(_) => SubDomainStatus::ExistingMintActive,
+    };
+    let domain_owner = query_name_owner(&domain, &c.cw721, &deps)?.owner;
+    let max_expiration =
+        ancestors_expiration(deps.storage, &domain_route)?.unwrap_or(domain_config.expiration);
+    let expiration = (env.block.time.seconds() + registrar.duration).min(max_expiration);
+    let resolver = new_resolver.unwrap_or_else(|| info.sender.clone());
+
+    let mut messages = match subdomain_status {
+        SubDomainStatus::NewSubdomain => register_new_subdomain(
+            c.cw721,
+            deps,
+            env,
+            domain.clone(),
+            subdomain.clone(),
+            resolver,
+            info.sender.clone(),
+            expiration,
+        )?,
+        SubDomainStatus::ExistingMintExpired => burn_remint_subdomain(
+            deps,
+            c.cw721,
+            env,
+            domain.clone(),
+            subdomain.clone(),
+            resolver,
+            info.sender.clone(),
+            expiration,
+        )?,
+        SubDomainStatus::ExistingMintActive => {
+            return Err(ContractError::NameTaken { name: domain_route })
+        }
+    };
+
+    messages.extend(split_payment(
+        &Addr::unchecked(domain_owner),
+        &c.wallet,
+        registrar.price,
+        &registrar.denom,
+        c.protocol_fee,
+    )?);
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "claim_subdomain")
+        .add_attribute("domain", domain)
+        .add_attribute("subdomain", subdomain))
+}
+
+// a zero price accepts no funds at all
+fn check_payment(info: &MessageInfo, price: Uint128, denom: &str) -> Result<(), ContractError> {
+    if price.is_zero() {
+        nonpayable(info)?;
+    } else {
+        let paid = must_pay(info, denom)?;
+        if paid != price {
+            return Err(ContractError::InvalidPayment { amount: paid });
+        }
+    }
+    Ok(())
+}
+
+// fuses only bind while the subdomain is active, an expired subdomain
+// returns to its parent
+fn check_fuse(deps: &DepsMut, env: &Env, name: &str, fuse: Fuse) -> Result<(), ContractError> {
+    if has_fuse(deps.storage, name, fuse)? && !is_expired(deps, name, &env.block) {
+        return Err(ContractError::FuseBurned {
+            fuse: format!("{:?}", fuse),
+        });
+    }
+    Ok(())
+}
+
+// resolver-only records have no NFT, so no owner to authorize and no
+// metadata to update
+fn check_minted(deps: &DepsMut, name: &str) -> Result<(), ContractError> {
+    match may_load_record(deps.storage, name)? {
+        Some(record) if !record.resolver_only => Ok(()),
+        _ => Err(ContractError::NameNotExists {
+            name: name.to_string(),
+        }),
+    }
+}
+
+// during a lease only the tenant controls the resolver and metadata
+fn check_controller(
+    deps: &mut DepsMut,
+    env: &Env,
+    name: &str,
+    owner: &str,
+    sender: &Addr,
+    scope: ManagerScope,
+) -> Result<(), ContractError> {
+    match active_lease(deps.storage, &env.block, name)? {
+        Some(lease) if lease.tenant != *sender => Err(ContractError::Unauthorized {}),
+        Some(_) => Ok(()),
+        None => check_authorized(deps, env, name, owner, sender, scope),
+    }
+}
+
+// the registry can't hand over a name while it is leased
+fn check_not_leased(deps: &DepsMut, env: &Env, name: &str) -> Result<(), ContractError> {
+    if active_lease(deps.storage, &env.block, name)?.is_some() {
+        return Err(ContractError::NameLeased {
+            name: name.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// check_owner only lets the NFT owner through. Moving the token, by a
+/// transfer, a sale or a lease, and delegating to managers are never left
+/// to a manager, whatever its scope
+fn check_owner(
+    deps: &mut DepsMut,
+    name: &str,
+    cw721: &Addr,
+    sender: &Addr,
+) -> Result<(), ContractError> {
+    if query_name_owner(name, cw721, deps)?.owner != *sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    prune_managers(deps.storage, name, sender.as_str())?;
+    Ok(())
+}
+
+/// sale_payment pays the proceeds of a sale to `seller`, minus the
+/// `Config::royalty` share sent to the protocol wallet
+fn sale_payment(
+    c: &Config,
+    seller: &Addr,
+    amount: Uint128,
+    denom: &str,
+) -> StdResult<Vec<CosmosMsg>> {
+    split_payment(seller, &c.wallet, amount, denom, c.royalty)
+}
+
+// the NFT owner or a manager it approved for `scope`, approvals of earlier
+// owners are dropped on the way so they can't come back with the NFT
+fn check_authorized(
+    deps: &mut DepsMut,
+    env: &Env,
+    name: &str,
+    owner: &str,
+    sender: &Addr,
+    scope: ManagerScope,
+) -> Result<(), ContractError> {
+    if !is_authorized(&deps.as_ref(), &env.block, name, owner, sender, scope)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    prune_managers(deps.storage, name, owner)?;
+    Ok(())
+}
+
+pub fn execute_set_renewal_terms(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    domain: String,
+    terms: Option<RenewalTerms>,
+) -> Result<Response, ContractError> {
+    let c: Config = name_config(deps.storage, &domain)?;
+    if is_expired(&deps, &domain, &env.block) {
+        return Err(ContractError::NameOwnershipExpired { name: domain });
+    }
+    let owner_response = query_name_owner(&domain, &c.cw721, &deps)?;
+    check_authorized(
+        &mut deps,
+        &env,
+        &domain,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Subdomains,
+    )?;
+
+    match terms {
+        Some(t) => {
+            if t.duration == 0 || t.denom.is_empty() {
+                return Err(ContractError::InvalidInput {});
+            }
+            RENEWAL_TERMS.save(deps.storage, &domain, &t)?;
+        }
+        None => RENEWAL_TERMS.remove(deps.storage, &domain),
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_renewal_terms")
+        .add_attribute("domain", domain))
+}
+
+/// Lets the holder of a subdomain extend it on the parent's renewal terms,
+/// up to the parent's expiration, paying the parent owner
+pub fn execute_renew_subdomain(
+    info: MessageInfo,
+    deps: DepsMut,
+    env: Env,
+    domain: String,
+    subdomain: String,
+) -> Result<Response, ContractError> {
+    let c: Config = name_config(deps.storage, &domain)?;
+    let domain_route: String = format!("{}.{}", subdomain, domain);
+
+    let terms =
+        RENEWAL_TERMS
+            .may_load(deps.storage, &domain)?
+            .ok_or(ContractError::RenewalClosed {
+                domain: domain.clone(),
+            })?;
+    check_payment(&info, terms.price, &terms.denom)?;
+
+    let domain_config =
+        may_load_record(deps.storage, &domain)?.ok_or(ContractError::NameNotExists {
+            name: domain.clone(),
+        })?;
+    let subdomain_config =
+        may_load_record(deps.storage, &domain_route)?.ok_or(ContractError::NameNotExists {
+            name: domain_route.clone(),
+        })?;
+    if domain_config.is_expired(&env.block)
+        || ancestors_expired(deps.storage, &domain_route, &env.block)?
+    {
+        return Err(ContractError::NameOwnershipExpired { name: domain });
+    }
+    if subdomain_config.is_expired(&env.block)
+        || is_stale(deps.storage, &domain_route, &subdomain_config)?
+    {
+        return Err(ContractError::NameOwnershipExpired { name: domain_route });
+    }
+    let subdomain_owner = query_name_owner(&domain_route, &c.cw721, &deps)?;
+    if subdomain_owner.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    let max_expiration =
+        ancestors_expiration(deps.storage, &domain_route)?.unwrap_or(domain_config.expiration);
+    let expiration = (subdomain_config.expiration + terms.duration).min(max_expiration);
+    // nothing left to extend before the parent expires
+    if expiration <= subdomain_config.expiration {
+        return Err(ContractError::InvalidInput {});
+    }
+
+    let domain_owner = query_name_owner(&domain, &c.cw721, &deps)?.owner;
+    let mut messages =
+        update_subdomain_expiry(c.cw721, deps, domain.clone(), subdomain.clone(), expiration)?;
+    messages.extend(split_payment(
+        &Addr::unchecked(domain_owner),
+        &c.wallet,
+        terms.price,
+        &terms.denom,
+        c.protocol_fee,
+    )?);
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "renew_subdomain")
+        .add_attribute("domain", domain_route)
+        .add_attribute("expiration", expiration.to_string()))
+}
+
+/// Removes subdomains of `domain` left over from a previous registration of
+/// it, along with everything below them. Anyone can prune, `start_after` and
+/// `limit` page through the subdomain index.
+pub fn execute_prune_subdomains(
+    deps: DepsMut,
+    domain: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let c: Config = name_config(deps.storage, &domain)?;
+    let limit = limit.unwrap_or(DEFAULT_PRUNE_LIMIT).min(MAX_PRUNE_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+    let labels = SUBDOMAINS
+        .prefix(domain.as_str())
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<String>>>()?;
+
+    let mut messages = Vec::new();
+    let mut pruned = vec![];
+    for label in labels.into_iter() {
+        let name = format!("{}.{}", label, domain);
+        let record = match may_load_record(deps.storage, &name)? {
+            Some(record) => record,
+            None => continue,
+        };
+        if !is_stale(deps.storage, &name, &record)? {
+            continue;
+        }
+        let mut names = subdomain_descendants(deps.storage, &name)?;
+        names.push(name);
+        messages.extend(remove_names(deps.storage, &names, &c.cw721)?);
+        pruned.extend(names);
+    }
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "prune_subdomains")
+        .add_attribute("domain", domain)
+        .add_attribute("pruned", pruned.join(",")))
+}
+
+/// Points `subdomain.domain.arch` at `resolver` without minting an NFT, the
+/// record stays under the parent owner's control until minted with
+/// `RegisterSubdomain`
+pub fn execute_set_resolver_only_subdomain(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    domain: String,
+    subdomain: String,
+    resolver: Addr,
+    expiration: u64,
+) -> Result<Response, ContractError> {
+    validate_subdomain(deps.storage, &domain, &subdomain)?;
+    let c: Config = name_config(deps.storage, &domain)?;
+    let domain_route: String = format!("{}.{}", subdomain, domain);
+
+    let domain_config =
+        may_load_record(deps.storage, &domain)?.ok_or(ContractError::NameNotExists {
+            name: domain.clone(),
+        })?;
+    if domain_config.is_expired(&env.block)
+        || ancestors_expired(deps.storage, &domain_route, &env.block)?
+    {
+        return Err(ContractError::NameOwnershipExpired { name: domain });
+    }
+    let owner_response = query_name_owner(&domain, &c.cw721, &deps)?;
+    check_authorized(
+        &mut deps,
+        &env,
+        &domain,
+        &owner_response.owner,
+        &info.sender,
+        ManagerScope::Subdomains,
+    )?;
+    let max_expiration =
+        ancestors_expiration(deps.storage, &domain_route)?.unwrap_or(domain_config.expiration);
+    let expiration = expiration.min(max_expiration);
+    if let Some(record) = may_load_record(deps.storage, &domain_route)? {
+        if !record.resolver_only && !is_expired(&deps, &domain_route, &env.block) {
+            return Err(ContractError::NameTaken { name: domain_route });
+        }
+        if resolver != record.resolver {
+            check_fuse(&deps, &env, &domain_route, Fuse::CannotChangeResolver)?;
+        }
+        if expiration < record.expiration {
+            check_fuse(&deps, &env, &domain_route, Fuse::CannotShortenExpiry)?;
+        }
+    }
+    let messages = set_resolver_only_subdomain(
+        deps,
+        c.cw721,
+        env,
+        domain.clone(),
+        subdomain.clone(),
+        resolver,
+        expiration,
+    )?;
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "set_resolver_only_subdomain")
+        .add_attribute("domain", domain)
+        .add_attribute("subdomain", subdomain))
+}
+
+/// Irreversibly gives up parent rights on an active subdomain
+pub fn execute_burn_fuses(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    domain: String,
+    subdomain: String,
+    fuses: Vec<Fuse>,
+) -> Result<Response, ContractError> {
+    let c: Config = name_config(deps.storage, &domain)?;
+    let domain_route: String = format!("{}.{}", subdomain, domain);
+    if is_expired(&deps, &domain, &env.block) {
+        return Err(ContractError::NameOwnershipExpired { name: domain });
+    }
+    if is_expired(&deps, &domain_route, &env.block) {
+        return Err(ContractError::NameOwnershipExpired { name: domain_route });
+    }
+    // fuses give up the owner's rights for good, so no manager can burn them
+    check_owner(&mut deps, &domain, &c.cw721, &info.sender)?;
+
+    let mut burned = FUSES
+        .may_load(deps.storage, &domain_route)?
+        .unwrap_or_default();
+    for fuse in fuses.into_iter() {
+        if !burned.contains(&fuse) {
+            burned.push(fuse);
+        }
+    }
+    FUSES.save(deps.storage, &domain_route, &burned)?;
+    Ok(Response::new()
+        .add_attribute("action", "burn_fuses")
+        .add_attribute("domain", domain_route))
+}
+
+pub fn execute_approve_manager(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    name: String,
+    manager: Addr,
+    scopes: Vec<ManagerScope>,
+    expires: Option<u64>,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let c: Config = name_config(deps.storage, &name)?;
+    if is_expired(&deps, &name, &env.block) {
+        return Err(ContractError::NameOwnershipExpired { name });
+    }
+    if scopes.is_empty() || expires.map_or(false, |e| e <= env.block.time.seconds()) {
+        return Err(ContractError::InvalidInput {});
+    }
+    check_owner(&mut deps, &name, &c.cw721, &info.sender)?;
+    let manager = deps.api.addr_validate(manager.as_str())?;
+    MANAGERS.save(
+        deps.storage,
+        (name.as_str(), &manager),
+        &Manager {
+            granted_by: info.sender,
+            scopes,
+            expires,
+        },
+    )?;
+    Ok(Response::new()
+        .add_attribute("action", "approve_manager")
+        .add_attribute("name", name)
+        .add_attribute("manager", manager))
+}
+
+pub fn execute_revoke_manager(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    name: String,
+    manager: Addr,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let c: Config = name_config(deps.storage, &name)?;
+    check_owner(&mut deps, &name, &c.cw721, &info.sender)?;
+    MANAGERS.remove(deps.storage, (name.as_str(), &manager));
+    Ok(Response::new()
+        .add_attribute("action", "revoke_manager")
+        .add_attribute("name", name)
+        .add_attribute("manager", manager))
+}
+
+/// execute_transfer_name moves the NFT of `name` to `recipient` and points
+/// the name at `new_resolver`, or the recipient, in the same transaction.
+/// The owner must have approved the registry as a spender of the token.
+pub fn execute_transfer_name(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    name: String,
+    recipient: Addr,
+    new_resolver: Option<Addr>,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let c: Config = name_config(deps.storage, &name)?;
+    let curr = may_load_record(deps.storage, &name)?
+        .ok_or(ContractError::NameNotExists { name: name.clone() })?;
+    if curr.is_expired(&env.block)
+        || is_stale(deps.storage, &name, &curr)?
+        || ancestors_expired(deps.storage, &name, &env.block)?
+    {
+        return Err(ContractError::NameOwnershipExpired { name });
+    }
+    check_owner(&mut deps, &name, &c.cw721, &info.sender)?;
+    check_not_leased(&deps, &env, &name)?;
+    let recipient = deps.api.addr_validate(recipient.as_str())?;
+    let new_resolver = match new_resolver {
+        Some(addr) => deps.api.addr_validate(addr.as_str())?,
+        None => recipient.clone(),
+    };
+
+    let messages = hand_over_name(deps, &c.cw721, &name, curr, &recipient, new_resolver)?;
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "transfer_name")
+        .add_attribute("domain", name)
+        .add_attribute("recipient", recipient))
+}
+
+pub fn execute_list_name(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    name: String,
+    price: Uint128,
+    denom: String,
+    expires: Option<u64>,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let c: Config = name_config(deps.storage, &name)?;
+    if is_expired(&deps, &name, &env.block) || ancestors_expired(deps.storage, &name, &env.block)? {
+        return Err(ContractError::NameOwnershipExpired { name });
+    }
+    if price.is_zero() || expires.map_or(false, |e| e <= env.block.time.seconds()) {
+        return Err(ContractError::InvalidInput {});
+    }
+    check_owner(&mut deps, &name, &c.cw721, &info.sender)?;
+    check_not_leased(&deps, &env, &name)?;
+    let listing = Listing {
+        seller: info.sender,
+        price,
+        denom,
+        expires,
+    };
+    LISTINGS.save(deps.storage, &name, &listing)?;
+    Ok(Response::new()
+        .add_attribute("action", "list_name")
+        .add_attribute("domain", name)
+        .add_attribute("price", format!("{}{}", listing.price, listing.denom)))
+}
+
+pub fn execute_cancel_listing(
+    info: MessageInfo,
+    deps: DepsMut,
+    name: String,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let c: Config = name_config(deps.storage, &name)?;
+    let listing = LISTINGS
+        .may_load(deps.storage, &name)?
+        .ok_or(ContractError::NotListed { name: name.clone() })?;
+    // the current owner can clear a listing left behind by a previous owner
+    if listing.seller != info.sender
+        && query_name_owner(&name, &c.cw721, &deps)?.owner != info.sender
+    {
+        return Err(ContractError::Unauthorized {});
+    }
+    LISTINGS.remove(deps.storage, &name);
+    Ok(Response::new()
+        .add_attribute("action", "cancel_listing")
+        .add_attribute("domain", name))
+}
+
+/// execute_buy_name pays the listing price to the seller and hands the name
+/// over to the buyer
+pub fn execute_buy_name(
+    info: MessageInfo,
+    deps: DepsMut,
+    env: Env,
+    name: String,
+    new_resolver: Option<Addr>,
+) -> Result<Response, ContractError> {
+    let c: Config = name_config(deps.storage, &name)?;
+    let listing = active_listing(&deps.as_ref(), &env, &name)?
+        .ok_or(ContractError::NotListed { name: name.clone() })?;
+    check_payment(&info, listing.price, &listing.denom)?;
+    check_not_leased(&deps, &env, &name)?;
+    let new_resolver = match new_resolver {
+        Some(addr) => deps.api.addr_validate(addr.as_str())?,
+        None => info.sender.clone(),
+    };
+    let curr = (may_load_record(deps.storage, &name)?).unwrap();
+
+    let mut messages = sale_payment(&c, &listing.seller, listing.price, &listing.denom)?;
+    messages.extend(hand_over_name(
+        deps,
+        &c.cw721,
+        &name,
+        curr,
+        &info.sender,
+        new_resolver,
+    )?);
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "buy_name")
+        .add_attribute("domain", name)
+        .add_attribute("buyer", info.sender))
+}
+
+pub fn execute_make_offer(
+    info: MessageInfo,
+    deps: DepsMut,
+    env: Env,
+    name: String,
+    expires: u64,
+) -> Result<Response, ContractError> {
+    let payment = one_coin(&info)?;
+    let curr = may_load_record(deps.storage, &name)?
+        .ok_or(ContractError::NameNotExists { name: name.clone() })?;
+    if curr.resolver_only
+        || curr.is_expired(&env.block)
+        || is_stale(deps.storage, &name, &curr)?
+        || ancestors_expired(deps.storage, &name, &env.block)?
+    {
+        return Err(ContractError::NameOwnershipExpired { name });
+    }
+    if expires <= env.block.time.seconds() {
+        return Err(ContractError::InvalidInput {});
+    }
+    let mut messages = Vec::new();
+    // a new offer replaces the earlier one of the same bidder
+    if let Some(previous) = OFFERS.may_load(deps.storage, (name.as_str(), &info.sender))? {
+        messages.push(send_coin(&info.sender, previous.amount, &previous.denom)?);
+    }
+    let offer = Offer {
+        amount: payment.amount,
+        denom: payment.denom,
+        expires,
+        rejected: false,
+    };
+    save_offer(deps.storage, &name, &info.sender, &offer)?;
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "make_offer")
+        .add_attribute("domain", name)
+        .add_attribute("bidder", info.sender)
+        .add_attribute("amount", format!("{}{}", offer.amount, offer.denom)))
+}
+
+/// execute_accept_offer pays the offer to the owner and hands the name over
+/// to the bidder
+pub fn execute_accept_offer(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    name: String,
+    bidder: Addr,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let c: Config = name_config(deps.storage, &name)?;
+    let offer = match OFFERS.may_load(deps.storage, (name.as_str(), &bidder))? {
+        Some(offer) if !offer.rejected && !offer.is_expired(&env.block) => offer,
+        _ => {
+            return Err(ContractError::OfferNotFound {
+                name,
+                bidder: bidder.to_string(),
+            })
+        }
+    };
+    let curr = may_load_record(deps.storage, &name)?
+        .ok_or(ContractError::NameNotExists { name: name.clone() })?;
+    if curr.is_expired(&env.block)
+        || is_stale(deps.storage, &name, &curr)?
+        || ancestors_expired(deps.storage, &name, &env.block)?
+    {
+        return Err(ContractError::NameOwnershipExpired { name });
+    }
+    check_owner(&mut deps, &name, &c.cw721, &info.sender)?;
+    check_not_leased(&deps, &env, &name)?;
+
+    remove_offer(deps.storage, &name, &bidder)?;
+    let mut messages = sale_payment(&c, &info.sender, offer.amount, &offer.denom)?;
+    messages.extend(hand_over_name(
+        deps,
+        &c.cw721,
+        &name,
+        curr,
+        &bidder,
+        bidder.clone(),
+    )?);
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "accept_offer")
+        .add_attribute("domain", name)
+        .add_attribute("bidder", bidder))
+}
+
+pub fn execute_reject_offer(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    name: String,
+    bidder: Addr,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let c: Config = name_config(deps.storage, &name)?;
+    let mut offer = OFFERS
+        .may_load(deps.storage, (name.as_str(), &bidder))?
+        .ok_or(ContractError::OfferNotFound {
+            name: name.clone(),
+            bidder: bidder.to_string(),
+        })?;
+    check_owner(&mut deps, &name, &c.cw721, &info.sender)?;
+    offer.rejected = true;
+    OFFERS.save(deps.storage, (name.as_str(), &bidder), &offer)?;
+    Ok(Response::new()
+        .add_attribute("action", "reject_offer")
+        .add_attribute("domain", name)
+        .add_attribute("bidder", bidder))
+}
+
+pub fn execute_withdraw_offer(
+    info: MessageInfo,
+    deps: DepsMut,
+    env: Env,
+    name: String,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let offer = OFFERS
+        .may_load(deps.storage, (name.as_str(), &info.sender))?
+        .ok_or(ContractError::OfferNotFound {
+            name: name.clone(),
+            bidder: info.sender.to_string(),
+        })?;
+    // open offers stay escrowed until they expire, unless the name lapsed
+    let name_active = match may_load_record(deps.storage, &name)? {
+        Some(record) => {
+            !record.is_expired(&env.block)
+                && !is_stale(deps.storage, &name, &record)?
+                && !ancestors_expired(deps.storage, &name, &env.block)?
+        }
+        None => false,
+    };
+    if !offer.rejected && !offer.is_expired(&env.block) && name_active {
+        return Err(ContractError::Unauthorized {});
+    }
+    remove_offer(deps.storage, &name, &info.sender)?;
+    let refund = send_coin(&info.sender, offer.amount, &offer.denom)?;
+    Ok(Response::new()
+        .add_message(refund)
+        .add_attribute("action", "withdraw_offer")
+        .add_attribute("domain", name)
+        .add_attribute("bidder", info.sender))
+}
+
+/// execute_lease_name lends `name` to `tenant` and points it at the tenant,
+/// the owner's resolver is kept on the lease and returns when it ends
+pub fn execute_lease_name(
+    info: MessageInfo,
+    mut deps: DepsMut,
+    env: Env,
+    name: String,
+    tenant: Addr,
+    expires: u64,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let c: Config = name_config(deps.storage, &name)?;
+    check_owner(&mut deps, &name, &c.cw721, &info.sender)?;
+    check_not_leased(&deps, &env, &name)?;
+    settle_lease(deps.storage, &env.block, &name)?;
+    let mut curr = may_load_record(deps.storage, &name)?
+        .ok_or(ContractError::NameNotExists { name: name.clone() })?;
+    if curr.is_expired(&env.block)
+        || is_stale(deps.storage, &name, &curr)?
+        || ancestors_expired(deps.storage, &name, &env.block)?
+    {
+        return Err(ContractError::NameOwnershipExpired { name });
+    }
+    // a lease can't outlive the registration
+    let max_expiration = ancestors_expiration(deps.storage, &name)?
+        .map_or(curr.expiration, |e| e.min(curr.expiration));
+    if expires <= env.block.time.seconds() || expires > max_expiration {
+        return Err(ContractError::InvalidInput {});
+    }
+    let tenant = deps.api.addr_validate(tenant.as_str())?;
+
+    let lease = Lease {
+        tenant: tenant.clone(),
+        expires,
+        resolver: curr.resolver.clone(),
+        resolver_contract: curr.resolver_contract.clone(),
+    };
+    LEASES.save(deps.storage, &name, &lease)?;
+    curr.resolver = tenant.clone();
+    curr.resolver_contract = None;
+    save_record(deps.storage, &name, &curr)?;
+    RESOLVER_OWNERS.save(deps.storage, &name, &info.sender)?;
+    Ok(Response::new()
+        .add_attribute("action", "lease_name")
+        .add_attribute("domain", name)
+        .add_attribute("tenant", tenant)
+        .add_attribute("expires", expires.to_string()))
+}
+
+pub fn execute_end_lease(
+    info: MessageInfo,
+    deps: DepsMut,
+    env: Env,
+    name: String,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let mut lease = LEASES
+        .may_load(deps.storage, &name)?
+        .ok_or(ContractError::InvalidInput {})?;
+    // anyone can settle an ended lease, only the tenant can end it early
+    if !lease.is_expired(&env.block) {
+        if lease.tenant != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+        lease.expires = env.block.time.seconds();
+        LEASES.save(deps.storage, &name, &lease)?;
+    }
+    settle_lease(deps.storage, &env.block, &name)?;
+    Ok(Response::new()
+        .add_attribute("action", "end_lease")
+        .add_attribute("domain", name))
+}
+
+pub fn execute_set_tld(
+    info: MessageInfo,
+    deps: DepsMut,
+    tld: String,
+    settings: Tld,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let c: Config = CONFIG.load(deps.storage)?;
+    if c.admin != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    // `.arch` is configured through the config, tlds are single ascii labels
+    let valid_tld = !tld.is_empty()
+        && tld != DEFAULT_TLD
+        && tld
+            .chars()
+            .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit());
+    if !valid_tld
+        || settings.base_cost.is_zero()
+        || settings.base_expiration == 0
+        || !settings.validation.is_valid()
+    {
+        return Err(ContractError::InvalidInput {});
+    }
+    TLDS.save(deps.storage, &tld, &settings)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_tld")
+        .add_attribute("tld", tld))
+}
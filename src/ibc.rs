@@ -0,0 +1,112 @@
+use crate::error::ContractError;
+use crate::msg::{ResolverAck, ResolverPacket};
+use crate::read_utils::{normalize_name, query_resolver, query_resolver_address};
+
+use cosmwasm_std::{
+    entry_point, from_slice, to_binary, Binary, Deps, DepsMut, Env, IbcBasicResponse, IbcChannel,
+    IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcOrder, IbcPacketAckMsg,
+    IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, StdResult,
+};
+
+pub const IBC_VERSION: &str = "archid-resolver-1";
+
+fn validate_channel(
+    channel: &IbcChannel,
+    counterparty_version: Option<&str>,
+) -> Result<(), ContractError> {
+    if channel.order != IbcOrder::Unordered {
+        return Err(ContractError::IbcOrderedChannel {});
+    }
+    for version in [Some(channel.version.as_str()), counterparty_version]
+        .iter()
+        .flatten()
+    {
+        if *version != IBC_VERSION {
+            return Err(ContractError::IbcInvalidVersion {
+                version: version.to_string(),
+                expected: IBC_VERSION.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_open(
+    _deps: DepsMut,
+    _env: Env,
+    msg: IbcChannelOpenMsg,
+) -> Result<(), ContractError> {
+    validate_channel(msg.channel(), msg.counterparty_version())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_connect(
+    _deps: DepsMut,
+    _env: Env,
+    msg: IbcChannelConnectMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    validate_channel(msg.channel(), msg.counterparty_version())?;
+    Ok(IbcBasicResponse::new()
+        .add_attribute("action", "ibc_connect")
+        .add_attribute("channel_id", &msg.channel().endpoint.channel_id))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_close(
+    _deps: DepsMut,
+    _env: Env,
+    msg: IbcChannelCloseMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    Ok(IbcBasicResponse::new()
+        .add_attribute("action", "ibc_close")
+        .add_attribute("channel_id", &msg.channel().endpoint.channel_id))
+}
+
+/// Answers resolve packets with the same data as the matching queries,
+/// failures are returned as an error ack rather than aborting the relay
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_receive(
+    deps: DepsMut,
+    env: Env,
+    msg: IbcPacketReceiveMsg,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let ack = match handle_packet(deps.as_ref(), env, &msg.packet.data) {
+        Ok(result) => ResolverAck::Result(result),
+        Err(e) => ResolverAck::Error(e.to_string()),
+    };
+    Ok(IbcReceiveResponse::new()
+        .set_ack(to_binary(&ack)?)
+        .add_attribute("action", "ibc_packet_receive"))
+}
+
+fn handle_packet(deps: Deps, env: Env, data: &Binary) -> StdResult<Binary> {
+    let packet: ResolverPacket = from_slice(data)?;
+    match packet {
+        ResolverPacket::ResolveRecord { name } => query_resolver(deps, env, normalize_name(&name)),
+        ResolverPacket::ResolveAddress {
+            address,
+            start_after,
+            limit,
+        } => query_resolver_address(deps, env, address, start_after, limit),
+    }
+}
+
+// the registry never sends packets, so there is nothing to acknowledge
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_ack(
+    _deps: DepsMut,
+    _env: Env,
+    _msg: IbcPacketAckMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    Ok(IbcBasicResponse::new())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_timeout(
+    _deps: DepsMut,
+    _env: Env,
+    _msg: IbcPacketTimeoutMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    Ok(IbcBasicResponse::new())
+}
@@ -1,20 +1,37 @@
 #![cfg(test)]
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_env, mock_ibc_channel_connect_ack, mock_ibc_channel_open_init,
+    mock_ibc_channel_open_try, mock_ibc_packet_recv,
+};
 use cosmwasm_std::{
-    to_binary, Addr, Coin, Empty, QueryRequest, StdError, Timestamp, Uint128, WasmQuery,
+    from_binary, to_binary, Addr, Binary, Coin, ContractResult, Decimal, Deps, DepsMut, Empty,
+    Env, IbcOrder, MessageInfo, QueryRequest, Response, StdError, StdResult, SystemResult,
+    Timestamp, Uint128, WasmQuery,
 };
+use cosmwasm_storage::{bucket, singleton};
 use cw_multi_test::{App, Contract, ContractWrapper, Executor};
 use serde::{de::DeserializeOwned, Serialize};
 
 use archid_token::{
-    Extension, InstantiateMsg as Cw721InstantiateMsg, Metadata, QueryMsg as Cw721QueryMsg,
+    ExecuteMsg as Cw721ExecuteMsg, Extension, InstantiateMsg as Cw721InstantiateMsg, Metadata,
+    QueryMsg as Cw721QueryMsg,
 };
 use cw721_updatable::{NftInfoResponse, NumTokensResponse, OwnerOfResponse};
 
+use crate::ibc::{ibc_channel_connect, ibc_channel_open, ibc_packet_receive, IBC_VERSION};
 use crate::msg::{
-    ExecuteMsg, InstantiateMsg, QueryMsg, RecordExpirationResponse, 
-    ResolveAddressResponse, ResolveRecordResponse,
+    AddressResponse, AllTextsResponse, Availability, AvailableBatchResponse, AvailableResponse, ExecuteMsg, FusesResponse, InstantiateMsg, LeaseResponse,
+    ListSubdomainsResponse, ListingResponse, ListingsResponse, ManagersResponse, MigrateMsg,
+    MetaDataUpdateMsg, NamesByOwnerResponse, OffersResponse, QueryMsg, RecordExpirationResponse,
+    RegistrarResponse, RenewalTermsResponse, ResolveAddressResponse, ResolveForChainResponse,
+    ResolveRecordResponse, ResolverAck, ResolverPacket, ResolverQueryMsg, TextResponse,
+    TldsResponse,
+};
+use crate::state::{
+    has_legacy_records, may_load_record, save_record, Config, Fuse, ManagerScope, NameRecord,
+    NameRules, Registrar, RegistrarAccess, RenewalTerms, Tld, TransferPolicy, ValidationRules,
+    ADDRESS_NAMES, CONFIG, CONFIG_KEY, NAME_RESOLVER_KEY, NAMES, RESOLVER_OWNERS,
 };
-use crate::state::Config;
 use crate::write_utils::DENOM;
 
 fn mock_app() -> App {
@@ -45,6 +62,35 @@ pub fn contract_archid() -> Box<dyn Contract<Empty>> {
     );
     Box::new(contract)
 }
+fn mock_resolver_instantiate(
+    _deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    _msg: Empty,
+) -> StdResult<Response> {
+    Ok(Response::default())
+}
+fn mock_resolver_query(_deps: Deps, _env: Env, msg: ResolverQueryMsg) -> StdResult<Binary> {
+    match msg {
+        ResolverQueryMsg::Address { .. } => to_binary(&AddressResponse {
+            address: Some(String::from("treasury")),
+        }),
+        ResolverQueryMsg::Text { key, .. } => to_binary(&TextResponse {
+            value: Some(format!("dynamic {}", key)),
+        }),
+        ResolverQueryMsg::ChainAddress { chain, .. } => to_binary(&AddressResponse {
+            address: Some(format!("{}1treasury", chain)),
+        }),
+    }
+}
+pub fn contract_resolver() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(
+        mock_resolver_instantiate,
+        mock_resolver_instantiate,
+        mock_resolver_query,
+    );
+    Box::new(contract)
+}
 fn create_name_service(
     router: &mut App,
     _owner: Addr,
@@ -61,6 +107,11 @@ fn create_name_service(
         cw721: _nft,
         base_cost: _base_cost,
         base_expiration: _base_expiration,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
     };
     let name_addr = router
         .instantiate_contract(contract_id, _owner, &msg, &[], "archID", None)
@@ -138,6 +189,11 @@ fn basic_domain_test() {
         cw721: nft.clone(),
         base_cost: Uint128::from(5000u64),
         base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
     };
     let update_msg = ExecuteMsg::UpdateConfig {
         config: update_config,
@@ -328,6 +384,8 @@ fn basic_domain_test() {
         name_service.clone(),
         QueryMsg::ResolveAddress {
             address: name_owner.clone(),
+            start_after: None,
+            limit: None,
         }
     )
     .unwrap();
@@ -372,6 +430,11 @@ fn test_expired_domains() {
         cw721: nft.clone(),
         base_cost: Uint128::from(5000u64),
         base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
     };
     let update_msg = ExecuteMsg::UpdateConfig {
         config: update_config,
@@ -513,6 +576,11 @@ fn test_subdomain_rules() {
         cw721: nft.clone(),
         base_cost: Uint128::from(5000u64),
         base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
     };
     let update_msg = ExecuteMsg::UpdateConfig {
         config: update_config,
@@ -627,6 +695,11 @@ fn test_remint_subdomain() {
         cw721: nft.clone(),
         base_cost: Uint128::from(5000u64),
         base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
     };
     let update_msg = ExecuteMsg::UpdateConfig {
         config: update_config,
@@ -738,3 +811,3874 @@ fn test_remint_subdomain() {
         &[],
     ).is_err());
 }
+
+#[test]
+fn test_names_by_owner() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("simpletest"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let current_time = get_block_time(&mut app);
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("simpletest"),
+        subdomain: String::from("subdomain"),
+        new_resolver: name_owner2.clone(),
+        new_owner: name_owner.clone(),
+        expiration: current_time + 43200,
+    };
+    let _subdomain = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &subdomain_msg,
+        &[],
+    );
+    // point the domain elsewhere, it must still be found by its nft owner
+    let update_resolver_msg = ExecuteMsg::UpdateResolver {
+        name: String::from("simpletest"),
+        new_resolver: name_owner2.clone(),
+    };
+    assert!(app
+        .execute_contract(
+            name_owner.clone(),
+            name_service.clone(),
+            &update_resolver_msg,
+            &[]
+        )
+        .is_ok());
+
+    let owned: NamesByOwnerResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::NamesByOwner {
+            owner: name_owner.clone(),
+            start_after: None,
+            limit: None,
+            tld: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(owned.names.len(), 2);
+    assert_eq!(owned.names[0].name, "simpletest.arch");
+    assert!(!owned.names[0].subdomain);
+    assert_eq!(owned.names[1].name, "subdomain.simpletest.arch");
+    assert!(owned.names[1].subdomain);
+    assert_eq!(owned.names[1].expiration, current_time + 43200);
+
+    let paged: NamesByOwnerResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::NamesByOwner {
+            owner: name_owner.clone(),
+            start_after: Some(String::from("simpletest.arch")),
+            limit: Some(1),
+            tld: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(paged.names.len(), 1);
+    assert_eq!(paged.names[0].name, "subdomain.simpletest.arch");
+
+    // subdomain lapses before the domain
+    increment_block_time(&mut app, current_time + 43201, 77);
+    let owned: NamesByOwnerResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::NamesByOwner {
+            owner: name_owner.clone(),
+            start_after: None,
+            limit: None,
+            tld: None,
+        },
+    )
+    .unwrap();
+    assert!(!owned.names[0].expired);
+    assert!(owned.names[1].expired);
+
+    let none_owned: NamesByOwnerResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::NamesByOwner {
+            owner: name_owner2.clone(),
+            start_after: None,
+            limit: None,
+            tld: None,
+        },
+    )
+    .unwrap();
+    assert!(none_owned.names.is_empty());
+}
+
+#[test]
+fn test_migrate_legacy_state() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    cw2::set_contract_version(&mut deps.storage, "crates.io:archid-registry", "1.0.2").unwrap();
+
+    // write state the way v1.0.x stored it
+    let legacy_config = Config {
+        admin: Addr::unchecked("owner"),
+        wallet: Addr::unchecked("wallet"),
+        cw721: Addr::unchecked("nft"),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    singleton(&mut deps.storage, CONFIG_KEY)
+        .save(&legacy_config)
+        .unwrap();
+    let names = ["alpha.arch", "beta.arch", "gamma.arch"];
+    for name in names.iter() {
+        let record = NameRecord {
+            resolver: Addr::unchecked("mintnames"),
+            created: 1,
+            expiration: 86401,
+            resolver_contract: None,
+            parent_epoch: 0,
+            resolver_only: false,
+        };
+        bucket(&mut deps.storage, NAME_RESOLVER_KEY)
+            .save(name.as_bytes(), &record)
+            .unwrap();
+    }
+    // the collection reports who holds every migrated name
+    deps.querier.update_wasm(|_| {
+        let owner = OwnerOfResponse {
+            owner: String::from("mintnames"),
+            approvals: vec![],
+        };
+        SystemResult::Ok(ContractResult::Ok(to_binary(&owner).unwrap()))
+    });
+
+    let msg = MigrateMsg {
+        limit: Some(2),
+        config: None,
+    };
+    let res = crate::contract::migrate(deps.as_mut(), env.clone(), msg).unwrap();
+    assert_eq!(res.attributes[1].value, "2");
+    assert_eq!(res.attributes[2].value, "false");
+    assert_eq!(CONFIG.load(&deps.storage).unwrap(), legacy_config);
+    assert!(NAMES.has(&deps.storage, "alpha.arch"));
+    assert!(!NAMES.has(&deps.storage, "gamma.arch"));
+    // records not reached yet still resolve through the legacy bucket
+    assert!(may_load_record(&deps.storage, "gamma.arch")
+        .unwrap()
+        .is_some());
+
+    // resume with the same code version
+    let msg = MigrateMsg {
+        limit: None,
+        config: None,
+    };
+    let res = crate::contract::migrate(deps.as_mut(), env.clone(), msg).unwrap();
+    assert_eq!(res.attributes[1].value, "1");
+    assert_eq!(res.attributes[2].value, "true");
+    assert!(NAMES.has(&deps.storage, "gamma.arch"));
+    assert!(!has_legacy_records(&deps.storage));
+    // migrated names fall under the transfer policy and reverse lookups
+    let mintnames = Addr::unchecked("mintnames");
+    for name in names.iter() {
+        assert_eq!(
+            RESOLVER_OWNERS.load(&deps.storage, *name).unwrap(),
+            mintnames
+        );
+        assert!(ADDRESS_NAMES.has(&deps.storage, (&mintnames, *name)));
+    }
+
+    // nothing left to resume
+    let msg = MigrateMsg {
+        limit: None,
+        config: None,
+    };
+    assert!(crate::contract::migrate(deps.as_mut(), env, msg).is_err());
+}
+
+#[test]
+fn test_migrate_semver() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let config = Config {
+        admin: Addr::unchecked("owner"),
+        wallet: Addr::unchecked("wallet"),
+        cw721: Addr::unchecked("nft"),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    CONFIG.save(&mut deps.storage, &config).unwrap();
+
+    // a newer version can't be downgraded
+    cw2::set_contract_version(&mut deps.storage, "crates.io:archid-registry", "9.0.0").unwrap();
+    let msg = MigrateMsg {
+        limit: None,
+        config: None,
+    };
+    assert!(crate::contract::migrate(deps.as_mut(), env.clone(), msg.clone()).is_err());
+
+    // a release candidate precedes its release, although it sorts after it as a string
+    cw2::set_contract_version(
+        &mut deps.storage,
+        "crates.io:archid-registry",
+        &format!("{}-rc.1", env!("CARGO_PKG_VERSION")),
+    )
+    .unwrap();
+    let invalid_config = Config {
+        royalty: Decimal::percent(150),
+        ..config.clone()
+    };
+    let msg = MigrateMsg {
+        limit: None,
+        config: Some(invalid_config),
+    };
+    assert!(crate::contract::migrate(deps.as_mut(), env.clone(), msg).is_err());
+    assert_eq!(CONFIG.load(&deps.storage).unwrap(), config);
+    let new_config = Config {
+        base_cost: Uint128::from(6000u64),
+        ..config
+    };
+    let msg = MigrateMsg {
+        limit: None,
+        config: Some(new_config.clone()),
+    };
+    assert!(crate::contract::migrate(deps.as_mut(), env, msg).is_ok());
+    assert_eq!(CONFIG.load(&deps.storage).unwrap(), new_config);
+    let version = cw2::get_contract_version(&deps.storage).unwrap();
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn test_text_records() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    mint_native(
+        &mut app,
+        name_owner2.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("simpletest"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let set_avatar_msg = ExecuteMsg::SetText {
+        name: String::from("simpletest"),
+        key: String::from("avatar"),
+        value: String::from("ipfs://avatar"),
+    };
+    let set_url_msg = ExecuteMsg::SetText {
+        name: String::from("simpletest"),
+        key: String::from("url"),
+        value: String::from("https://archid.app"),
+    };
+    // only the name owner can set text records
+    assert!(app
+        .execute_contract(
+            name_owner2.clone(),
+            name_service.clone(),
+            &set_avatar_msg,
+            &[]
+        )
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &set_avatar_msg, &[])
+        .is_ok());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &set_url_msg, &[])
+        .is_ok());
+    let too_long_msg = ExecuteMsg::SetText {
+        name: String::from("simpletest"),
+        key: String::from("description"),
+        value: "a".repeat(1025),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &too_long_msg, &[])
+        .is_err());
+
+    let avatar: TextResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Text {
+            name: String::from("simpletest.arch"),
+            key: String::from("avatar"),
+        },
+    )
+    .unwrap();
+    assert_eq!(avatar.value, Some(String::from("ipfs://avatar")));
+
+    let texts: AllTextsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::AllTexts {
+            name: String::from("simpletest.arch"),
+            start_after: Some(String::from("avatar")),
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(texts.texts.len(), 1);
+    assert_eq!(texts.texts[0].key, "url");
+
+    // an empty value removes the record
+    let remove_msg = ExecuteMsg::SetText {
+        name: String::from("simpletest"),
+        key: String::from("avatar"),
+        value: String::from(""),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &remove_msg, &[])
+        .is_ok());
+    let avatar: TextResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Text {
+            name: String::from("simpletest.arch"),
+            key: String::from("avatar"),
+        },
+    )
+    .unwrap();
+    assert_eq!(avatar.value, None);
+
+    // records don't carry over to the next registrant
+    let current_time = get_block_time(&mut app);
+    increment_block_time(&mut app, current_time + 86401, 777);
+    let _reregister = app.execute_contract(
+        name_owner2.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let texts: AllTextsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::AllTexts {
+            name: String::from("simpletest.arch"),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert!(texts.texts.is_empty());
+}
+
+#[test]
+fn test_chain_addresses() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let mock = Addr::unchecked("testtesttest");
+    let archway_addr = Addr::unchecked("archway1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc52fs6vt");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("simpletest"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let update_resolver_msg = ExecuteMsg::UpdateResolver {
+        name: String::from("simpletest"),
+        new_resolver: archway_addr.clone(),
+    };
+    let _update = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &update_resolver_msg,
+        &[],
+    );
+
+    // addresses must match the chain they're set for
+    let bad_prefix_msg = ExecuteMsg::SetChainAddress {
+        name: String::from("simpletest"),
+        chain: String::from("osmo"),
+        address: String::from("cosmos19q5j52ev95hz7vp3xgengdfkxuurjw3m2jlqd4"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &bad_prefix_msg, &[])
+        .is_err());
+    let bad_evm_msg = ExecuteMsg::SetChainAddress {
+        name: String::from("simpletest"),
+        chain: String::from("eip155:1"),
+        address: String::from("0x1234"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &bad_evm_msg, &[])
+        .is_err());
+
+    let set_cosmos_msg = ExecuteMsg::SetChainAddress {
+        name: String::from("simpletest"),
+        chain: String::from("cosmos"),
+        address: String::from("cosmos19q5j52ev95hz7vp3xgengdfkxuurjw3m2jlqd4"),
+    };
+    assert!(app
+        .execute_contract(
+            name_owner2.clone(),
+            name_service.clone(),
+            &set_cosmos_msg,
+            &[]
+        )
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &set_cosmos_msg, &[])
+        .is_ok());
+    let set_evm_msg = ExecuteMsg::SetChainAddress {
+        name: String::from("simpletest"),
+        chain: String::from("eip155:1"),
+        address: String::from("0x52908400098527886E0F7030069857D2E4169EE7"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &set_evm_msg, &[])
+        .is_ok());
+
+    let cosmos: ResolveForChainResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveForChain {
+            name: String::from("simpletest.arch"),
+            chain: String::from("cosmos"),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        cosmos.address,
+        Some(String::from("cosmos19q5j52ev95hz7vp3xgengdfkxuurjw3m2jlqd4"))
+    );
+    assert!(!cosmos.derived);
+    let evm: ResolveForChainResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveForChain {
+            name: String::from("simpletest.arch"),
+            chain: String::from("eip155:1"),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        evm.address,
+        Some(String::from("0x52908400098527886E0F7030069857D2E4169EE7"))
+    );
+
+    // without a record the archway resolver is re-encoded
+    let osmo: ResolveForChainResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveForChain {
+            name: String::from("simpletest.arch"),
+            chain: String::from("osmo"),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        osmo.address,
+        Some(String::from("osmo1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5helwsw"))
+    );
+    assert!(osmo.derived);
+    let evm2: ResolveForChainResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveForChain {
+            name: String::from("simpletest.arch"),
+            chain: String::from("eip155:10"),
+        },
+    )
+    .unwrap();
+    assert_eq!(evm2.address, None);
+
+    // every name holds a bounded number of chain addresses
+    for i in 2..32 {
+        let set_msg = ExecuteMsg::SetChainAddress {
+            name: String::from("simpletest"),
+            chain: format!("eip155:{}", i),
+            address: String::from("0x52908400098527886E0F7030069857D2E4169EE7"),
+        };
+        assert!(app
+            .execute_contract(name_owner.clone(), name_service.clone(), &set_msg, &[])
+            .is_ok());
+    }
+    let over_cap_msg = ExecuteMsg::SetChainAddress {
+        name: String::from("simpletest"),
+        chain: String::from("eip155:32"),
+        address: String::from("0x52908400098527886E0F7030069857D2E4169EE7"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &over_cap_msg, &[])
+        .is_err());
+    // overwriting an existing chain stays allowed at the cap
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &set_evm_msg, &[])
+        .is_ok());
+}
+
+#[test]
+fn test_ibc_resolver_port() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let config = Config {
+        admin: Addr::unchecked("owner"),
+        wallet: Addr::unchecked("wallet"),
+        cw721: Addr::unchecked("nft"),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    CONFIG.save(&mut deps.storage, &config).unwrap();
+    let resolver = Addr::unchecked("mintnames");
+    let record = NameRecord {
+        resolver: resolver.clone(),
+        created: env.block.time.seconds(),
+        expiration: env.block.time.seconds() + 86400,
+        resolver_contract: None,
+        parent_epoch: 0,
+        resolver_only: false,
+    };
+    save_record(&mut deps.storage, "simpletest.arch", &record).unwrap();
+    save_record(&mut deps.storage, "other.arch", &record).unwrap();
+    save_record(&mut deps.storage, "moved.arch", &record).unwrap();
+    let moved = NameRecord {
+        resolver: Addr::unchecked("elsewhere"),
+        ..record.clone()
+    };
+    save_record(&mut deps.storage, "moved.arch", &moved).unwrap();
+
+    // handshake
+    let bad_order = mock_ibc_channel_open_init("channel-0", IbcOrder::Ordered, IBC_VERSION);
+    assert!(ibc_channel_open(deps.as_mut(), env.clone(), bad_order).is_err());
+    let bad_version = mock_ibc_channel_open_try("channel-0", IbcOrder::Unordered, "ics20-1");
+    assert!(ibc_channel_open(deps.as_mut(), env.clone(), bad_version).is_err());
+    let open = mock_ibc_channel_open_try("channel-0", IbcOrder::Unordered, IBC_VERSION);
+    assert!(ibc_channel_open(deps.as_mut(), env.clone(), open).is_ok());
+    let connect = mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, IBC_VERSION);
+    assert!(ibc_channel_connect(deps.as_mut(), env.clone(), connect).is_ok());
+
+    // resolve
+    let packet = ResolverPacket::ResolveRecord {
+        name: String::from("simpletest.arch"),
+    };
+    let msg = mock_ibc_packet_recv("channel-0", &packet).unwrap();
+    let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg).unwrap();
+    let ack: ResolverAck = from_binary(&res.acknowledgement).unwrap();
+    let resolved: ResolveRecordResponse = match ack {
+        ResolverAck::Result(data) => from_binary(&data).unwrap(),
+        ResolverAck::Error(e) => panic!("unexpected error ack {}", e),
+    };
+    assert_eq!(resolved.address, Some(resolver.to_string()));
+    assert_eq!(resolved.expiration, record.expiration);
+
+    // reverse resolution is answered one page at a time
+    let packet = ResolverPacket::ResolveAddress {
+        address: resolver.clone(),
+        start_after: None,
+        limit: Some(1),
+    };
+    let msg = mock_ibc_packet_recv("channel-0", &packet).unwrap();
+    let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg).unwrap();
+    let ack: ResolverAck = from_binary(&res.acknowledgement).unwrap();
+    let page: ResolveAddressResponse = match ack {
+        ResolverAck::Result(data) => from_binary(&data).unwrap(),
+        ResolverAck::Error(e) => panic!("unexpected error ack {}", e),
+    };
+    assert_eq!(page.names, Some(vec![String::from("other.arch")]));
+    let packet = ResolverPacket::ResolveAddress {
+        address: resolver.clone(),
+        start_after: Some(String::from("other.arch")),
+        limit: None,
+    };
+    let msg = mock_ibc_packet_recv("channel-0", &packet).unwrap();
+    let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg).unwrap();
+    let ack: ResolverAck = from_binary(&res.acknowledgement).unwrap();
+    let page: ResolveAddressResponse = match ack {
+        ResolverAck::Result(data) => from_binary(&data).unwrap(),
+        ResolverAck::Error(e) => panic!("unexpected error ack {}", e),
+    };
+    // a name leaves the index of its previous resolver
+    assert_eq!(page.names, Some(vec![String::from("simpletest.arch")]));
+
+    // unknown names are answered with an error ack
+    let packet = ResolverPacket::ResolveRecord {
+        name: String::from("unknown.arch"),
+    };
+    let msg = mock_ibc_packet_recv("channel-0", &packet).unwrap();
+    let res = ibc_packet_receive(deps.as_mut(), env, msg).unwrap();
+    let ack: ResolverAck = from_binary(&res.acknowledgement).unwrap();
+    assert!(matches!(ack, ResolverAck::Error(_)));
+}
+
+#[test]
+fn test_resolver_contract() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let resolver_id = app.store_code(contract_resolver());
+    let resolver_contract = app
+        .instantiate_contract(resolver_id, owner.clone(), &Empty {}, &[], "resolver", None)
+        .unwrap();
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("simpletest"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let delegate_msg = ExecuteMsg::SetResolverContract {
+        name: String::from("simpletest"),
+        contract: Some(resolver_contract.clone()),
+    };
+    assert!(app
+        .execute_contract(name_owner2.clone(), name_service.clone(), &delegate_msg, &[])
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &delegate_msg, &[])
+        .is_ok());
+
+    let resolve: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("simpletest.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(resolve.address, Some(String::from("treasury")));
+    let text: TextResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Text {
+            name: String::from("simpletest.arch"),
+            key: String::from("url"),
+        },
+    )
+    .unwrap();
+    assert_eq!(text.value, Some(String::from("dynamic url")));
+    let chain: ResolveForChainResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveForChain {
+            name: String::from("simpletest.arch"),
+            chain: String::from("osmo"),
+        },
+    )
+    .unwrap();
+    assert_eq!(chain.address, Some(String::from("osmo1treasury")));
+    assert!(!chain.derived);
+
+    // back to resolving from the registry
+    let undelegate_msg = ExecuteMsg::SetResolverContract {
+        name: String::from("simpletest"),
+        contract: None,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &undelegate_msg, &[])
+        .is_ok());
+    let resolve: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("simpletest.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(resolve.address, Some(name_owner.to_string()));
+}
+
+#[test]
+fn test_wildcard_subdomains() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let mock = Addr::unchecked("testtesttest");
+    let team_wallet = Addr::unchecked("teamwallet");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("team"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let current_time = get_block_time(&mut app);
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("team"),
+        subdomain: String::from("alice"),
+        new_resolver: name_owner2.clone(),
+        new_owner: name_owner2.clone(),
+        expiration: current_time + 43200,
+    };
+    let _subdomain = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &subdomain_msg,
+        &[],
+    );
+
+    // unknown subdomains don't resolve before a wildcard is set
+    let unknown: Result<ResolveRecordResponse, StdError> = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("bob.team.arch"),
+        },
+    );
+    assert!(unknown.is_err());
+
+    let invalid_msg = ExecuteMsg::SetWildcard {
+        domain: String::from("team"),
+        resolver: Addr::unchecked("Team Wallet"),
+        resolver_contract: None,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &invalid_msg, &[])
+        .is_err());
+    let wildcard_msg = ExecuteMsg::SetWildcard {
+        domain: String::from("team"),
+        resolver: team_wallet.clone(),
+        resolver_contract: None,
+    };
+    assert!(app
+        .execute_contract(name_owner2.clone(), name_service.clone(), &wildcard_msg, &[])
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &wildcard_msg, &[])
+        .is_ok());
+
+    let bob: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("bob.team.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(bob.address, Some(team_wallet.to_string()));
+    // explicit subdomain records take precedence
+    let alice: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("alice.team.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(alice.address, Some(name_owner2.to_string()));
+
+    let remove_msg = ExecuteMsg::RemoveWildcard {
+        domain: String::from("team"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &remove_msg, &[])
+        .is_ok());
+    let removed: Result<ResolveRecordResponse, StdError> = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("bob.team.arch"),
+        },
+    );
+    assert!(removed.is_err());
+}
+
+#[test]
+fn test_multi_level_subdomains() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("acme"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let current_time = get_block_time(&mut app);
+    let finance_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("acme"),
+        subdomain: String::from("finance"),
+        new_resolver: name_owner2.clone(),
+        new_owner: name_owner2.clone(),
+        expiration: current_time + 43200,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &finance_msg, &[])
+        .is_ok());
+
+    // only the owner of finance.acme.arch controls its children
+    let payroll_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("finance.acme"),
+        subdomain: String::from("payroll"),
+        new_resolver: mock.clone(),
+        new_owner: mock.clone(),
+        expiration: current_time + 80000,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &payroll_msg, &[])
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner2.clone(), name_service.clone(), &payroll_msg, &[])
+        .is_ok());
+
+    // capped by the earliest ancestor expiration
+    let payroll: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("payroll.finance.acme.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(payroll.address, Some(mock.to_string()));
+    assert_eq!(payroll.expiration, current_time + 43200);
+    let payroll_owner: OwnerOfResponse = query(
+        &mut app,
+        nft.clone(),
+        Cw721QueryMsg::<Extension>::OwnerOf {
+            token_id: String::from("payroll.finance.acme.arch"),
+            include_expired: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(payroll_owner.owner, mock.to_string());
+    let finance_cw721: NftInfoResponse<Extension> = query(
+        &mut app,
+        nft.clone(),
+        Cw721QueryMsg::<Extension>::NftInfo {
+            token_id: String::from("finance.acme.arch"),
+        },
+    )
+    .unwrap();
+    let finance_subdomains = finance_cw721.extension.unwrap().subdomains.unwrap();
+    assert_eq!(finance_subdomains.len(), 1);
+    assert_eq!(finance_subdomains[0].name, Some(String::from("payroll")));
+
+    // removing finance.acme.arch takes payroll with it
+    increment_block_time(&mut app, current_time + 43201, 77);
+    let remove_msg = ExecuteMsg::RemoveSubdomain {
+        domain: String::from("acme"),
+        subdomain: String::from("finance"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &remove_msg, &[])
+        .is_ok());
+    let removed: Result<ResolveRecordResponse, StdError> = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("payroll.finance.acme.arch"),
+        },
+    );
+    assert!(removed.is_err());
+    let total: NumTokensResponse = query(
+        &mut app,
+        nft.clone(),
+        Cw721QueryMsg::<Extension>::NumTokens {},
+    )
+    .unwrap();
+    assert_eq!(total.count, 1);
+}
+
+#[test]
+fn test_public_subdomain_registrar() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let member = Addr::unchecked("member");
+    let outsider = Addr::unchecked("outsider");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    mint_native(
+        &mut app,
+        member.to_string(),
+        String::from(DENOM),
+        Uint128::from(5000u128),
+    );
+    mint_native(
+        &mut app,
+        outsider.to_string(),
+        String::from(DENOM),
+        Uint128::from(5000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::percent(10),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("dao"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let registrar = Registrar {
+        price: Uint128::from(1000u128),
+        denom: String::from(DENOM),
+        duration: 3600,
+        min_length: 3,
+        max_length: 10,
+        access: RegistrarAccess::Deny(vec![outsider.clone()]),
+    };
+    let claim_msg = ExecuteMsg::ClaimSubdomain {
+        domain: String::from("dao"),
+        subdomain: String::from("member"),
+        new_resolver: None,
+    };
+    let fee = [Coin {
+        denom: String::from(DENOM),
+        amount: Uint128::from(1000u128),
+    }];
+    // closed until the domain owner opens it
+    assert!(app
+        .execute_contract(member.clone(), name_service.clone(), &claim_msg, &fee)
+        .is_err());
+
+    let open_msg = ExecuteMsg::OpenRegistrar {
+        domain: String::from("dao"),
+        registrar: registrar.clone(),
+    };
+    assert!(app
+        .execute_contract(member.clone(), name_service.clone(), &open_msg, &[])
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &open_msg, &[])
+        .is_ok());
+    let res: RegistrarResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Registrar {
+            domain: String::from("dao.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.registrar, Some(registrar));
+
+    // wrong amount, label rules and the deny list are enforced
+    assert!(app
+        .execute_contract(
+            member.clone(),
+            name_service.clone(),
+            &claim_msg,
+            &[Coin {
+                denom: String::from(DENOM),
+                amount: Uint128::from(500u128),
+            }],
+        )
+        .is_err());
+    let long_msg = ExecuteMsg::ClaimSubdomain {
+        domain: String::from("dao"),
+        subdomain: String::from("averylonglabel"),
+        new_resolver: None,
+    };
+    assert!(app
+        .execute_contract(member.clone(), name_service.clone(), &long_msg, &fee)
+        .is_err());
+    let outsider_msg = ExecuteMsg::ClaimSubdomain {
+        domain: String::from("dao"),
+        subdomain: String::from("outsider"),
+        new_resolver: None,
+    };
+    assert!(app
+        .execute_contract(outsider.clone(), name_service.clone(), &outsider_msg, &fee)
+        .is_err());
+
+    let current_time = get_block_time(&mut app);
+    assert!(app
+        .execute_contract(member.clone(), name_service.clone(), &claim_msg, &fee)
+        .is_ok());
+    let owner_query: Cw721QueryMsg<Extension> = Cw721QueryMsg::OwnerOf {
+        token_id: String::from("member.dao.arch"),
+        include_expired: None,
+    };
+    let nft_owner: OwnerOfResponse = query(&mut app, nft.clone(), owner_query).unwrap();
+    assert_eq!(nft_owner.owner, member.to_string());
+    let resolved: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("member.dao.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(resolved.address, Some(member.to_string()));
+    assert_eq!(resolved.expiration, current_time + 3600);
+    // the fee is split between the domain owner and the protocol wallet
+    let owner_balance = app.wrap().query_balance(&name_owner, DENOM).unwrap();
+    assert_eq!(owner_balance.amount, Uint128::from(5900u128));
+    let wallet_balance = app.wrap().query_balance(&wallet, DENOM).unwrap();
+    assert_eq!(wallet_balance.amount, Uint128::from(100u128));
+
+    // a claimed subdomain can't be claimed again while active
+    assert!(app
+        .execute_contract(member.clone(), name_service.clone(), &claim_msg, &fee)
+        .is_err());
+
+    let close_msg = ExecuteMsg::CloseRegistrar {
+        domain: String::from("dao"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &close_msg, &[])
+        .is_ok());
+    let res: RegistrarResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Registrar {
+            domain: String::from("dao.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.registrar, None);
+}
+
+#[test]
+fn test_list_subdomains() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    // subdomains are indexed by the registry without touching NFT metadata
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: false,
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("guild"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let current_time = get_block_time(&mut app);
+    for label in ["carol", "alice", "bob"].iter() {
+        let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+            domain: String::from("guild"),
+            subdomain: String::from(*label),
+            new_resolver: name_owner2.clone(),
+            new_owner: name_owner2.clone(),
+            expiration: current_time + 43200,
+        };
+        assert!(app
+            .execute_contract(name_owner.clone(), name_service.clone(), &subdomain_msg, &[])
+            .is_ok());
+    }
+
+    let page: ListSubdomainsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ListSubdomains {
+            domain: String::from("guild.arch"),
+            start_after: None,
+            limit: Some(2),
+        },
+    )
+    .unwrap();
+    let labels: Vec<String> = page.subdomains.iter().map(|s| s.label.clone()).collect();
+    assert_eq!(labels, vec![String::from("alice"), String::from("bob")]);
+    assert_eq!(page.subdomains[0].name, String::from("alice.guild.arch"));
+    assert_eq!(page.subdomains[0].resolver, name_owner2);
+    assert_eq!(page.subdomains[0].expiration, current_time + 43200);
+    let page: ListSubdomainsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ListSubdomains {
+            domain: String::from("guild.arch"),
+            start_after: Some(String::from("bob")),
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(page.subdomains.len(), 1);
+    assert_eq!(page.subdomains[0].label, String::from("carol"));
+
+    let guild_cw721: NftInfoResponse<Extension> = query(
+        &mut app,
+        nft.clone(),
+        Cw721QueryMsg::<Extension>::NftInfo {
+            token_id: String::from("guild.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(guild_cw721.extension.unwrap().subdomains, Some(vec![]));
+
+    let remove_msg = ExecuteMsg::RemoveSubdomain {
+        domain: String::from("guild"),
+        subdomain: String::from("bob"),
+    };
+    increment_block_time(&mut app, current_time + 43201, 77);
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &remove_msg, &[])
+        .is_ok());
+    let page: ListSubdomainsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ListSubdomains {
+            domain: String::from("guild.arch"),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    let labels: Vec<String> = page.subdomains.iter().map(|s| s.label.clone()).collect();
+    assert_eq!(labels, vec![String::from("alice"), String::from("carol")]);
+    assert!(page.subdomains.iter().all(|s| s.expired));
+}
+
+#[test]
+fn test_subdomain_renewal() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    mint_native(
+        &mut app,
+        name_owner2.to_string(),
+        String::from(DENOM),
+        Uint128::from(5000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::percent(10),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("club"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let current_time = get_block_time(&mut app);
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("club"),
+        subdomain: String::from("member"),
+        new_resolver: name_owner2.clone(),
+        new_owner: name_owner2.clone(),
+        expiration: current_time + 1000,
+    };
+    let _subdomain = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &subdomain_msg,
+        &[],
+    );
+
+    let renew_msg = ExecuteMsg::RenewSubdomain {
+        domain: String::from("club"),
+        subdomain: String::from("member"),
+    };
+    let fee = [Coin {
+        denom: String::from(DENOM),
+        amount: Uint128::from(500u128),
+    }];
+    // holders can't renew until the parent publishes terms
+    assert!(app
+        .execute_contract(name_owner2.clone(), name_service.clone(), &renew_msg, &fee)
+        .is_err());
+
+    let terms = RenewalTerms {
+        price: Uint128::from(500u128),
+        denom: String::from(DENOM),
+        duration: 50000,
+    };
+    let terms_msg = ExecuteMsg::SetRenewalTerms {
+        domain: String::from("club"),
+        terms: terms.clone(),
+    };
+    assert!(app
+        .execute_contract(name_owner2.clone(), name_service.clone(), &terms_msg, &[])
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &terms_msg, &[])
+        .is_ok());
+    let res: RenewalTermsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::RenewalTerms {
+            domain: String::from("club.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.terms, Some(terms));
+
+    // only the subdomain owner renews
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &renew_msg, &fee)
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner2.clone(), name_service.clone(), &renew_msg, &fee)
+        .is_ok());
+    let member: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("member.club.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(member.expiration, current_time + 51000);
+
+    // capped by the parent expiration, and refused once there
+    assert!(app
+        .execute_contract(name_owner2.clone(), name_service.clone(), &renew_msg, &fee)
+        .is_ok());
+    let member: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("member.club.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(member.expiration, current_time + 86400);
+    assert!(app
+        .execute_contract(name_owner2.clone(), name_service.clone(), &renew_msg, &fee)
+        .is_err());
+
+    let owner_balance = app.wrap().query_balance(&name_owner, DENOM).unwrap();
+    assert_eq!(owner_balance.amount, Uint128::from(5900u128));
+    let wallet_balance = app.wrap().query_balance(&wallet, DENOM).unwrap();
+    assert_eq!(wallet_balance.amount, Uint128::from(100u128));
+}
+
+#[test]
+fn test_stale_subdomains_after_reregistration() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let new_owner = Addr::unchecked("newowner");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    mint_native(
+        &mut app,
+        new_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("dao"),
+    };
+    let fee = [Coin {
+        denom: String::from(DENOM),
+        amount: Uint128::from(5000u128),
+    }];
+    let _register =
+        app.execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &fee);
+    let current_time = get_block_time(&mut app);
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("dao"),
+        subdomain: String::from("alice"),
+        new_resolver: name_owner2.clone(),
+        new_owner: name_owner2.clone(),
+        expiration: current_time + 43200,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &subdomain_msg, &[])
+        .is_ok());
+
+    // dao.arch lapses and changes hands
+    increment_block_time(&mut app, current_time + 86401, 77);
+    assert!(app
+        .execute_contract(new_owner.clone(), name_service.clone(), &register_msg, &fee)
+        .is_ok());
+
+    // the previous owner's subdomain is treated as missing
+    let stale: Result<ResolveRecordResponse, StdError> = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("alice.dao.arch"),
+        },
+    );
+    assert!(stale.is_err());
+    let wildcard_msg = ExecuteMsg::SetWildcard {
+        domain: String::from("dao"),
+        resolver: new_owner.clone(),
+        resolver_contract: None,
+    };
+    assert!(app
+        .execute_contract(new_owner.clone(), name_service.clone(), &wildcard_msg, &[])
+        .is_ok());
+    let alice: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("alice.dao.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(alice.address, Some(new_owner.to_string()));
+    let listed: ListSubdomainsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ListSubdomains {
+            domain: String::from("dao.arch"),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert!(listed.subdomains[0].expired);
+
+    // anyone can prune stale subdomains
+    let prune_msg = ExecuteMsg::PruneSubdomains {
+        domain: String::from("dao"),
+        start_after: None,
+        limit: None,
+    };
+    assert!(app
+        .execute_contract(mock.clone(), name_service.clone(), &prune_msg, &[])
+        .is_ok());
+    let listed: ListSubdomainsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ListSubdomains {
+            domain: String::from("dao.arch"),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert!(listed.subdomains.is_empty());
+    let total: NumTokensResponse = query(
+        &mut app,
+        nft.clone(),
+        Cw721QueryMsg::<Extension>::NumTokens {},
+    )
+    .unwrap();
+    assert_eq!(total.count, 1);
+
+    // and the new owner can hand the label out again
+    let current_time = get_block_time(&mut app);
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("dao"),
+        subdomain: String::from("alice"),
+        new_resolver: new_owner.clone(),
+        new_owner: new_owner.clone(),
+        expiration: current_time + 43200,
+    };
+    assert!(app
+        .execute_contract(new_owner.clone(), name_service.clone(), &subdomain_msg, &[])
+        .is_ok());
+    let alice: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("alice.dao.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(alice.address, Some(new_owner.to_string()));
+}
+
+#[test]
+fn test_resolver_only_subdomains() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("corp"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let current_time = get_block_time(&mut app);
+    let record_msg = ExecuteMsg::SetResolverOnlySubdomain {
+        domain: String::from("corp"),
+        subdomain: String::from("desk"),
+        resolver: mock.clone(),
+        expiration: current_time + 43200,
+    };
+    assert!(app
+        .execute_contract(name_owner2.clone(), name_service.clone(), &record_msg, &[])
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &record_msg, &[])
+        .is_ok());
+    let desk: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("desk.corp.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(desk.address, Some(mock.to_string()));
+
+    // no NFT and no metadata rewrite
+    let total: NumTokensResponse = query(
+        &mut app,
+        nft.clone(),
+        Cw721QueryMsg::<Extension>::NumTokens {},
+    )
+    .unwrap();
+    assert_eq!(total.count, 1);
+    let corp_cw721: NftInfoResponse<Extension> = query(
+        &mut app,
+        nft.clone(),
+        Cw721QueryMsg::<Extension>::NftInfo {
+            token_id: String::from("corp.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(corp_cw721.extension.unwrap().subdomains, Some(vec![]));
+
+    // the parent owner repoints it in place
+    let record_msg = ExecuteMsg::SetResolverOnlySubdomain {
+        domain: String::from("corp"),
+        subdomain: String::from("desk"),
+        resolver: name_owner2.clone(),
+        expiration: current_time + 43200,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &record_msg, &[])
+        .is_ok());
+    let listed: ListSubdomainsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ListSubdomains {
+            domain: String::from("corp.arch"),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(listed.subdomains[0].resolver, name_owner2);
+    assert!(!listed.subdomains[0].minted);
+
+    // and can upgrade it to a minted subdomain
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("corp"),
+        subdomain: String::from("desk"),
+        new_resolver: name_owner2.clone(),
+        new_owner: name_owner2.clone(),
+        expiration: current_time + 43200,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &subdomain_msg, &[])
+        .is_ok());
+    let owner_query: Cw721QueryMsg<Extension> = Cw721QueryMsg::OwnerOf {
+        token_id: String::from("desk.corp.arch"),
+        include_expired: None,
+    };
+    let nft_owner: OwnerOfResponse = query(&mut app, nft.clone(), owner_query).unwrap();
+    assert_eq!(nft_owner.owner, name_owner2.to_string());
+    let listed: ListSubdomainsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ListSubdomains {
+            domain: String::from("corp.arch"),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert!(listed.subdomains[0].minted);
+    // minted subdomains can't be turned back into resolver-only records
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &record_msg, &[])
+        .is_err());
+
+    // resolver-only records are removed without a burn
+    let record_msg = ExecuteMsg::SetResolverOnlySubdomain {
+        domain: String::from("corp"),
+        subdomain: String::from("printer"),
+        resolver: mock.clone(),
+        expiration: current_time + 43200,
+    };
+    let _record = app.execute_contract(name_owner.clone(), name_service.clone(), &record_msg, &[]);
+    // without an NFT a resolver-only record can't parent subdomains or hold metadata
+    let child_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("printer.corp"),
+        subdomain: String::from("tray"),
+        new_resolver: mock.clone(),
+        new_owner: mock.clone(),
+        expiration: current_time + 43200,
+    };
+    let extend_msg = ExecuteMsg::ExtendSubdomainExpiry {
+        domain: String::from("printer.corp"),
+        subdomain: String::from("tray"),
+        expiration: current_time + 43200,
+    };
+    let remove_child_msg = ExecuteMsg::RemoveSubdomain {
+        domain: String::from("printer.corp"),
+        subdomain: String::from("tray"),
+    };
+    let metadata_msg = ExecuteMsg::UpdateUserDomainData {
+        name: String::from("printer.corp"),
+        metadata_update: MetaDataUpdateMsg {
+            description: None,
+            image: None,
+            accounts: None,
+            websites: None,
+        },
+    };
+    for msg in [child_msg, extend_msg, remove_child_msg, metadata_msg].iter() {
+        assert!(app
+            .execute_contract(name_owner.clone(), name_service.clone(), msg, &[])
+            .is_err());
+    }
+    let remove_msg = ExecuteMsg::RemoveSubdomain {
+        domain: String::from("corp"),
+        subdomain: String::from("printer"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &remove_msg, &[])
+        .is_ok());
+    let removed: Result<ResolveRecordResponse, StdError> = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("printer.corp.arch"),
+        },
+    );
+    assert!(removed.is_err());
+
+    // a minted subdomain goes with its resolver-only children, which have
+    // no NFT to burn
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("corp"),
+        subdomain: String::from("lab"),
+        new_resolver: name_owner.clone(),
+        new_owner: name_owner.clone(),
+        expiration: current_time + 43200,
+    };
+    let _subdomain = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &subdomain_msg, &[])
+        .unwrap();
+    let record_msg = ExecuteMsg::SetResolverOnlySubdomain {
+        domain: String::from("lab.corp"),
+        subdomain: String::from("bench"),
+        resolver: mock.clone(),
+        expiration: current_time + 43200,
+    };
+    let _record = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &record_msg, &[])
+        .unwrap();
+    let remove_msg = ExecuteMsg::RemoveSubdomain {
+        domain: String::from("corp"),
+        subdomain: String::from("lab"),
+    };
+    let _remove = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &remove_msg, &[])
+        .unwrap();
+    let removed: Result<ResolveRecordResponse, StdError> = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("bench.lab.corp.arch"),
+        },
+    );
+    assert!(removed.is_err());
+}
+
+#[test]
+fn test_subdomain_fuses() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("vault"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let current_time = get_block_time(&mut app);
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("vault"),
+        subdomain: String::from("alice"),
+        new_resolver: name_owner2.clone(),
+        new_owner: name_owner2.clone(),
+        expiration: current_time + 43200,
+    };
+    let _subdomain = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &subdomain_msg,
+        &[],
+    );
+
+    let fuse_msg = ExecuteMsg::BurnFuses {
+        domain: String::from("vault"),
+        subdomain: String::from("alice"),
+        fuses: vec![Fuse::CannotRemove, Fuse::CannotChangeResolver],
+    };
+    assert!(app
+        .execute_contract(name_owner2.clone(), name_service.clone(), &fuse_msg, &[])
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &fuse_msg, &[])
+        .is_ok());
+    let res: FusesResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Fuses {
+            name: String::from("alice.vault.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.fuses,
+        vec![Fuse::CannotRemove, Fuse::CannotChangeResolver]
+    );
+
+    // the parent can no longer take the subdomain back
+    let remove_msg = ExecuteMsg::RemoveSubdomain {
+        domain: String::from("vault"),
+        subdomain: String::from("alice"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &remove_msg, &[])
+        .is_err());
+    // fuses bind the parent, the holder still repoints its own subdomain
+    let resolver_msg = ExecuteMsg::UpdateResolver {
+        name: String::from("alice.vault"),
+        new_resolver: mock.clone(),
+    };
+    assert!(app
+        .execute_contract(name_owner2.clone(), name_service.clone(), &resolver_msg, &[])
+        .is_ok());
+
+    // a pinned resolver-only subdomain can't be repointed by the parent
+    let record_msg = ExecuteMsg::SetResolverOnlySubdomain {
+        domain: String::from("vault"),
+        subdomain: String::from("bob"),
+        resolver: mock.clone(),
+        expiration: current_time + 43200,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &record_msg, &[])
+        .is_ok());
+    let fuse_msg = ExecuteMsg::BurnFuses {
+        domain: String::from("vault"),
+        subdomain: String::from("bob"),
+        fuses: vec![Fuse::CannotChangeResolver],
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &fuse_msg, &[])
+        .is_ok());
+    let record_msg = ExecuteMsg::SetResolverOnlySubdomain {
+        domain: String::from("vault"),
+        subdomain: String::from("bob"),
+        resolver: name_owner2.clone(),
+        expiration: current_time + 43200,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &record_msg, &[])
+        .is_err());
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("vault"),
+        subdomain: String::from("bob"),
+        new_resolver: name_owner2.clone(),
+        new_owner: name_owner2.clone(),
+        expiration: current_time + 43200,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &subdomain_msg, &[])
+        .is_err());
+
+    // a fuse deeper down also holds against removing one of its ancestors
+    let team_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("vault"),
+        subdomain: String::from("team"),
+        new_resolver: name_owner.clone(),
+        new_owner: name_owner.clone(),
+        expiration: current_time + 43200,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &team_msg, &[])
+        .is_ok());
+    let member_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("team.vault"),
+        subdomain: String::from("carol"),
+        new_resolver: name_owner2.clone(),
+        new_owner: name_owner2.clone(),
+        expiration: current_time + 43200,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &member_msg, &[])
+        .is_ok());
+    let fuse_msg = ExecuteMsg::BurnFuses {
+        domain: String::from("team.vault"),
+        subdomain: String::from("carol"),
+        fuses: vec![Fuse::CannotRemove],
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &fuse_msg, &[])
+        .is_ok());
+    let remove_team_msg = ExecuteMsg::RemoveSubdomain {
+        domain: String::from("vault"),
+        subdomain: String::from("team"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &remove_team_msg, &[])
+        .is_err());
+    let carol: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("carol.team.vault.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(carol.address, Some(name_owner2.to_string()));
+
+    // fuses lapse with the subdomain
+    increment_block_time(&mut app, current_time + 43201, 77);
+    let res: FusesResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Fuses {
+            name: String::from("alice.vault.arch"),
+        },
+    )
+    .unwrap();
+    assert!(res.fuses.is_empty());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &remove_msg, &[])
+        .is_ok());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &remove_team_msg, &[])
+        .is_ok());
+}
+
+#[test]
+fn test_approved_managers() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let name_owner2 = Addr::unchecked("mintothernames");
+    let manager = Addr::unchecked("manager");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("studio"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+
+    let approve_msg = ExecuteMsg::ApproveManager {
+        name: String::from("studio"),
+        manager: manager.clone(),
+        scopes: vec![ManagerScope::Resolver],
+        expires: None,
+    };
+    // only the NFT owner can approve managers
+    assert!(app
+        .execute_contract(manager.clone(), name_service.clone(), &approve_msg, &[])
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &approve_msg, &[])
+        .is_ok());
+    let res: ManagersResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Managers {
+            name: String::from("studio.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.managers.len(), 1);
+    assert_eq!(res.managers[0].manager, manager);
+
+    // the manager can act within its scope
+    let resolver_msg = ExecuteMsg::UpdateResolver {
+        name: String::from("studio"),
+        new_resolver: manager.clone(),
+    };
+    assert!(app
+        .execute_contract(manager.clone(), name_service.clone(), &resolver_msg, &[])
+        .is_ok());
+    let res: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("studio.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.address, Some(manager.to_string()));
+    // but not outside of it
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("studio"),
+        subdomain: String::from("music"),
+        new_resolver: manager.clone(),
+        new_owner: manager.clone(),
+        expiration: get_block_time(&mut app) + 43200,
+    };
+    assert!(app
+        .execute_contract(manager.clone(), name_service.clone(), &subdomain_msg, &[])
+        .is_err());
+
+    // burning fuses gives up the owner's rights, so it is never delegated
+    let subdomain_manager = Addr::unchecked("subdomainmanager");
+    let approve_subdomains_msg = ExecuteMsg::ApproveManager {
+        name: String::from("studio"),
+        manager: subdomain_manager.clone(),
+        scopes: vec![ManagerScope::Subdomains],
+        expires: None,
+    };
+    assert!(app
+        .execute_contract(
+            name_owner.clone(),
+            name_service.clone(),
+            &approve_subdomains_msg,
+            &[]
+        )
+        .is_ok());
+    let art_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("studio"),
+        subdomain: String::from("art"),
+        new_resolver: subdomain_manager.clone(),
+        new_owner: name_owner.clone(),
+        expiration: get_block_time(&mut app) + 43200,
+    };
+    assert!(app
+        .execute_contract(subdomain_manager.clone(), name_service.clone(), &art_msg, &[])
+        .is_ok());
+    let fuse_msg = ExecuteMsg::BurnFuses {
+        domain: String::from("studio"),
+        subdomain: String::from("art"),
+        fuses: vec![Fuse::CannotRemove],
+    };
+    assert!(app
+        .execute_contract(subdomain_manager.clone(), name_service.clone(), &fuse_msg, &[])
+        .is_err());
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &fuse_msg, &[])
+        .is_ok());
+
+    // the approval lapses once the NFT changes hands
+    let transfer_msg: Cw721ExecuteMsg = Cw721ExecuteMsg::TransferNft {
+        recipient: name_owner2.to_string(),
+        token_id: String::from("studio.arch"),
+    };
+    let _transfer = app
+        .execute_contract(name_owner.clone(), nft.clone(), &transfer_msg, &[])
+        .unwrap();
+    assert!(app
+        .execute_contract(manager.clone(), name_service.clone(), &resolver_msg, &[])
+        .is_err());
+    let res: ManagersResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Managers {
+            name: String::from("studio.arch"),
+        },
+    )
+    .unwrap();
+    assert!(res.managers.is_empty());
+
+    // and stays revoked when the NFT returns to the owner that granted it
+    let owner2_resolver_msg = ExecuteMsg::UpdateResolver {
+        name: String::from("studio"),
+        new_resolver: name_owner2.clone(),
+    };
+    assert!(app
+        .execute_contract(
+            name_owner2.clone(),
+            name_service.clone(),
+            &owner2_resolver_msg,
+            &[]
+        )
+        .is_ok());
+    let transfer_back_msg: Cw721ExecuteMsg = Cw721ExecuteMsg::TransferNft {
+        recipient: name_owner.to_string(),
+        token_id: String::from("studio.arch"),
+    };
+    let _transfer = app
+        .execute_contract(name_owner2.clone(), nft.clone(), &transfer_back_msg, &[])
+        .unwrap();
+    assert!(app
+        .execute_contract(manager.clone(), name_service.clone(), &resolver_msg, &[])
+        .is_err());
+    let res: ManagersResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Managers {
+            name: String::from("studio.arch"),
+        },
+    )
+    .unwrap();
+    assert!(res.managers.is_empty());
+}
+
+#[test]
+fn test_transfer_policy() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let buyer = Addr::unchecked("buyer");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let mut update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::ResetToOwner,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config.clone(),
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("market"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let resolve_msg = QueryMsg::ResolveRecord {
+        name: String::from("market.arch"),
+    };
+    let res: ResolveRecordResponse =
+        query(&mut app, name_service.clone(), resolve_msg.clone()).unwrap();
+    assert_eq!(res.address, Some(name_owner.to_string()));
+
+    // renewing keeps the resolver that was set
+    let resolver_msg = ExecuteMsg::UpdateResolver {
+        name: String::from("market"),
+        new_resolver: mock.clone(),
+    };
+    let _update = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &resolver_msg, &[])
+        .unwrap();
+    let renew_msg = ExecuteMsg::RenewRegistration {
+        name: String::from("market"),
+    };
+    let _renew = app
+        .execute_contract(
+            name_owner.clone(),
+            name_service.clone(),
+            &renew_msg,
+            &[Coin {
+                denom: String::from(DENOM),
+                amount: Uint128::from(5000u128),
+            }],
+        )
+        .unwrap();
+    let res: ResolveRecordResponse =
+        query(&mut app, name_service.clone(), resolve_msg.clone()).unwrap();
+    assert_eq!(res.address, Some(mock.to_string()));
+
+    let text_msg = ExecuteMsg::SetText {
+        name: String::from("market"),
+        key: String::from("url"),
+        value: String::from("https://archid.app"),
+    };
+    let _text = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &text_msg, &[])
+        .unwrap();
+    let chain_msg = ExecuteMsg::SetChainAddress {
+        name: String::from("market"),
+        chain: String::from("cosmos"),
+        address: String::from("cosmos19q5j52ev95hz7vp3xgengdfkxuurjw3m2jlqd4"),
+    };
+    let _chain = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &chain_msg, &[])
+        .unwrap();
+    let text_query = QueryMsg::Text {
+        name: String::from("market.arch"),
+        key: String::from("url"),
+    };
+    let chain_query = QueryMsg::ResolveForChain {
+        name: String::from("market.arch"),
+        chain: String::from("cosmos"),
+    };
+
+    let transfer_msg: Cw721ExecuteMsg = Cw721ExecuteMsg::TransferNft {
+        recipient: buyer.to_string(),
+        token_id: String::from("market.arch"),
+    };
+    let _transfer = app
+        .execute_contract(name_owner.clone(), nft.clone(), &transfer_msg, &[])
+        .unwrap();
+
+    // the name follows the NFT to its buyer
+    let res: ResolveRecordResponse =
+        query(&mut app, name_service.clone(), resolve_msg.clone()).unwrap();
+    assert_eq!(res.address, Some(buyer.to_string()));
+    // records set by the seller don't follow it
+    let res: TextResponse = query(&mut app, name_service.clone(), text_query.clone()).unwrap();
+    assert_eq!(res.value, None);
+    let res: ResolveForChainResponse =
+        query(&mut app, name_service.clone(), chain_query.clone()).unwrap();
+    assert_eq!(res.address, None);
+    // and the seller's reverse lookup drops it
+    let res: ResolveAddressResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveAddress {
+            address: name_owner.clone(),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.names, Some(vec![]));
+
+    // stale names don't resolve at all
+    update_config.transfer_policy = TransferPolicy::MarkStale;
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+    let res: ResolveRecordResponse =
+        query(&mut app, name_service.clone(), resolve_msg.clone()).unwrap();
+    assert_eq!(res.address, None);
+    let res: ResolveForChainResponse = query(&mut app, name_service.clone(), chain_query).unwrap();
+    assert_eq!(res.address, None);
+    let res: TextResponse = query(&mut app, name_service.clone(), text_query).unwrap();
+    assert_eq!(res.value, None);
+
+    // until the new owner sets the resolver
+    let resolver_msg = ExecuteMsg::UpdateResolver {
+        name: String::from("market"),
+        new_resolver: mock.clone(),
+    };
+    let _update = app
+        .execute_contract(buyer.clone(), name_service.clone(), &resolver_msg, &[])
+        .unwrap();
+    let res: ResolveRecordResponse = query(&mut app, name_service.clone(), resolve_msg).unwrap();
+    assert_eq!(res.address, Some(mock.to_string()));
+}
+
+#[test]
+fn test_transfer_name() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let buyer = Addr::unchecked("buyer");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("shop"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let text_msg = ExecuteMsg::SetText {
+        name: String::from("shop"),
+        key: String::from("url"),
+        value: String::from("https://archid.app"),
+    };
+    let _text = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &text_msg, &[])
+        .unwrap();
+
+    let transfer_msg = ExecuteMsg::TransferName {
+        name: String::from("shop"),
+        recipient: buyer.clone(),
+        new_resolver: Some(mock.clone()),
+    };
+    // the registry can't move the token before it is approved
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &transfer_msg, &[])
+        .is_err());
+    let approve_msg: Cw721ExecuteMsg = Cw721ExecuteMsg::Approve {
+        spender: name_service.to_string(),
+        token_id: String::from("shop.arch"),
+        expires: None,
+    };
+    let _approve = app
+        .execute_contract(name_owner.clone(), nft.clone(), &approve_msg, &[])
+        .unwrap();
+    // and only the owner can transfer
+    assert!(app
+        .execute_contract(buyer.clone(), name_service.clone(), &transfer_msg, &[])
+        .is_err());
+    let _transfer = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &transfer_msg, &[])
+        .unwrap();
+
+    let owner_query: Cw721QueryMsg<Extension> = Cw721QueryMsg::OwnerOf {
+        token_id: String::from("shop.arch"),
+        include_expired: None,
+    };
+    let res: OwnerOfResponse = query(&mut app, nft.clone(), owner_query).unwrap();
+    assert_eq!(res.owner, buyer.to_string());
+    let res: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("shop.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.address, Some(mock.to_string()));
+    // the seller's text records are cleared
+    let res: AllTextsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::AllTexts {
+            name: String::from("shop.arch"),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert!(res.texts.is_empty());
+
+    // expired names can't be transferred
+    let current_time = get_block_time(&mut app);
+    increment_block_time(&mut app, current_time + 86401, 77);
+    let approve_msg: Cw721ExecuteMsg = Cw721ExecuteMsg::Approve {
+        spender: name_service.to_string(),
+        token_id: String::from("shop.arch"),
+        expires: None,
+    };
+    let _approve = app.execute_contract(buyer.clone(), nft.clone(), &approve_msg, &[]);
+    let transfer_msg = ExecuteMsg::TransferName {
+        name: String::from("shop"),
+        recipient: name_owner.clone(),
+        new_resolver: None,
+    };
+    assert!(app
+        .execute_contract(buyer.clone(), name_service.clone(), &transfer_msg, &[])
+        .is_err());
+}
+
+#[test]
+fn test_name_marketplace() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let buyer = Addr::unchecked("buyer");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    mint_native(
+        &mut app,
+        buyer.to_string(),
+        String::from(DENOM),
+        Uint128::from(1000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::percent(10),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("gallery"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+
+    let list_msg = ExecuteMsg::ListName {
+        name: String::from("gallery"),
+        price: Uint128::from(1000u128),
+        denom: String::from(DENOM),
+        expires: None,
+    };
+    // only the NFT owner can list
+    assert!(app
+        .execute_contract(buyer.clone(), name_service.clone(), &list_msg, &[])
+        .is_err());
+    let _list = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &list_msg, &[])
+        .unwrap();
+    let approve_msg: Cw721ExecuteMsg = Cw721ExecuteMsg::Approve {
+        spender: name_service.to_string(),
+        token_id: String::from("gallery.arch"),
+        expires: None,
+    };
+    let _approve = app
+        .execute_contract(name_owner.clone(), nft.clone(), &approve_msg, &[])
+        .unwrap();
+    let res: ListingsResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Listings {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.listings.len(), 1);
+    assert_eq!(res.listings[0].name, "gallery.arch");
+
+    let buy_msg = ExecuteMsg::BuyName {
+        name: String::from("gallery"),
+        new_resolver: None,
+    };
+    // the listing price must be paid in full
+    assert!(app
+        .execute_contract(
+            buyer.clone(),
+            name_service.clone(),
+            &buy_msg,
+            &[Coin {
+                denom: String::from(DENOM),
+                amount: Uint128::from(500u128),
+            }],
+        )
+        .is_err());
+    let _buy = app
+        .execute_contract(
+            buyer.clone(),
+            name_service.clone(),
+            &buy_msg,
+            &[Coin {
+                denom: String::from(DENOM),
+                amount: Uint128::from(1000u128),
+            }],
+        )
+        .unwrap();
+
+    let owner_balance = app.wrap().query_balance(&name_owner, DENOM).unwrap();
+    assert_eq!(owner_balance.amount, Uint128::from(5900u128));
+    let wallet_balance = app.wrap().query_balance(&wallet, DENOM).unwrap();
+    assert_eq!(wallet_balance.amount, Uint128::from(100u128));
+    let owner_query: Cw721QueryMsg<Extension> = Cw721QueryMsg::OwnerOf {
+        token_id: String::from("gallery.arch"),
+        include_expired: None,
+    };
+    let res: OwnerOfResponse = query(&mut app, nft.clone(), owner_query).unwrap();
+    assert_eq!(res.owner, buyer.to_string());
+    let res: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("gallery.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.address, Some(buyer.to_string()));
+    let listing_query = QueryMsg::Listing {
+        name: String::from("gallery.arch"),
+    };
+    let res: ListingResponse = query(&mut app, name_service.clone(), listing_query.clone()).unwrap();
+    assert_eq!(res.listing, None);
+
+    // listings lapse with the name
+    let _list = app
+        .execute_contract(buyer.clone(), name_service.clone(), &list_msg, &[])
+        .unwrap();
+    let res: ListingResponse = query(&mut app, name_service.clone(), listing_query.clone()).unwrap();
+    assert!(res.listing.is_some());
+    let current_time = get_block_time(&mut app);
+    increment_block_time(&mut app, current_time + 86401, 77);
+    let res: ListingResponse = query(&mut app, name_service.clone(), listing_query).unwrap();
+    assert_eq!(res.listing, None);
+}
+
+#[test]
+fn test_escrowed_offers() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let bidder = Addr::unchecked("bidder");
+    let bidder2 = Addr::unchecked("otherbidder");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    mint_native(
+        &mut app,
+        bidder.to_string(),
+        String::from(DENOM),
+        Uint128::from(1000u128),
+    );
+    mint_native(
+        &mut app,
+        bidder2.to_string(),
+        String::from(DENOM),
+        Uint128::from(1000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("atelier"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+
+    let current_time = get_block_time(&mut app);
+    let offer_msg = ExecuteMsg::MakeOffer {
+        name: String::from("atelier"),
+        expires: current_time + 3600,
+    };
+    let offer_funds = [Coin {
+        denom: String::from(DENOM),
+        amount: Uint128::from(1000u128),
+    }];
+    let _offer = app
+        .execute_contract(bidder.clone(), name_service.clone(), &offer_msg, &offer_funds)
+        .unwrap();
+    let _offer = app
+        .execute_contract(bidder2.clone(), name_service.clone(), &offer_msg, &offer_funds)
+        .unwrap();
+    let res: OffersResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::OffersByName {
+            name: String::from("atelier.arch"),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.offers.len(), 2);
+    let res: OffersResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::OffersByBidder {
+            bidder: bidder.clone(),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.offers.len(), 1);
+    assert_eq!(res.offers[0].name, "atelier.arch");
+    assert_eq!(res.offers[0].offer.amount, Uint128::from(1000u128));
+
+    // fee withdrawals can't reach the escrowed offers
+    let withdraw_fees_msg = ExecuteMsg::Withdraw {
+        amount: Uint128::from(7000u128),
+    };
+    assert!(app
+        .execute_contract(owner.clone(), name_service.clone(), &withdraw_fees_msg, &[])
+        .is_err());
+    let withdraw_fees_msg = ExecuteMsg::Withdraw {
+        amount: Uint128::from(5000u128),
+    };
+    let _withdraw_fees = app
+        .execute_contract(owner.clone(), name_service.clone(), &withdraw_fees_msg, &[])
+        .unwrap();
+    let wallet_balance = app.wrap().query_balance(&wallet, DENOM).unwrap();
+    assert_eq!(wallet_balance.amount, Uint128::from(5000u128));
+
+    // open offers stay in escrow
+    let withdraw_msg = ExecuteMsg::WithdrawOffer {
+        name: String::from("atelier"),
+    };
+    assert!(app
+        .execute_contract(bidder2.clone(), name_service.clone(), &withdraw_msg, &[])
+        .is_err());
+    // until the owner rejects them
+    let reject_msg = ExecuteMsg::RejectOffer {
+        name: String::from("atelier"),
+        bidder: bidder2.clone(),
+    };
+    let _reject = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &reject_msg, &[])
+        .unwrap();
+    let _withdraw = app
+        .execute_contract(bidder2.clone(), name_service.clone(), &withdraw_msg, &[])
+        .unwrap();
+    let bidder_balance = app.wrap().query_balance(&bidder2, DENOM).unwrap();
+    assert_eq!(bidder_balance.amount, Uint128::from(1000u128));
+
+    let approve_msg: Cw721ExecuteMsg = Cw721ExecuteMsg::Approve {
+        spender: name_service.to_string(),
+        token_id: String::from("atelier.arch"),
+        expires: None,
+    };
+    let _approve = app
+        .execute_contract(name_owner.clone(), nft.clone(), &approve_msg, &[])
+        .unwrap();
+    let accept_msg = ExecuteMsg::AcceptOffer {
+        name: String::from("atelier"),
+        bidder: bidder.clone(),
+    };
+    assert!(app
+        .execute_contract(bidder.clone(), name_service.clone(), &accept_msg, &[])
+        .is_err());
+    let _accept = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &accept_msg, &[])
+        .unwrap();
+
+    let owner_balance = app.wrap().query_balance(&name_owner, DENOM).unwrap();
+    assert_eq!(owner_balance.amount, Uint128::from(6000u128));
+    let owner_query: Cw721QueryMsg<Extension> = Cw721QueryMsg::OwnerOf {
+        token_id: String::from("atelier.arch"),
+        include_expired: None,
+    };
+    let res: OwnerOfResponse = query(&mut app, nft.clone(), owner_query).unwrap();
+    assert_eq!(res.owner, bidder.to_string());
+    let res: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("atelier.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.address, Some(bidder.to_string()));
+    let res: OffersResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::OffersByName {
+            name: String::from("atelier.arch"),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert!(res.offers.is_empty());
+}
+
+#[test]
+fn test_name_leases() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let tenant = Addr::unchecked("tenant");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("loft"),
+    };
+    let _register = app.execute_contract(
+        name_owner.clone(),
+        name_service.clone(),
+        &register_msg,
+        &[Coin {
+            denom: String::from(DENOM),
+            amount: Uint128::from(5000u128),
+        }],
+    );
+    let text_msg = ExecuteMsg::SetText {
+        name: String::from("loft"),
+        key: String::from("url"),
+        value: String::from("https://archid.app"),
+    };
+    let _text = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &text_msg, &[])
+        .unwrap();
+    let text_query = QueryMsg::Text {
+        name: String::from("loft.arch"),
+        key: String::from("url"),
+    };
+
+    let current_time = get_block_time(&mut app);
+    let lease_msg = ExecuteMsg::LeaseName {
+        name: String::from("loft"),
+        tenant: tenant.clone(),
+        expires: current_time + 3600,
+    };
+    assert!(app
+        .execute_contract(tenant.clone(), name_service.clone(), &lease_msg, &[])
+        .is_err());
+    let _lease = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &lease_msg, &[])
+        .unwrap();
+    let resolve_msg = QueryMsg::ResolveRecord {
+        name: String::from("loft.arch"),
+    };
+    let res: ResolveRecordResponse =
+        query(&mut app, name_service.clone(), resolve_msg.clone()).unwrap();
+    assert_eq!(res.address, Some(tenant.to_string()));
+    // the tenant doesn't see the owner's records
+    let res: TextResponse = query(&mut app, name_service.clone(), text_query.clone()).unwrap();
+    assert_eq!(res.value, None);
+
+    // the tenant controls the resolver and records, the owner doesn't
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &text_msg, &[])
+        .is_err());
+    let tenant_text_msg = ExecuteMsg::SetText {
+        name: String::from("loft"),
+        key: String::from("url"),
+        value: String::from("https://tenant.example"),
+    };
+    let _text = app
+        .execute_contract(tenant.clone(), name_service.clone(), &tenant_text_msg, &[])
+        .unwrap();
+    let res: TextResponse = query(&mut app, name_service.clone(), text_query.clone()).unwrap();
+    assert_eq!(res.value, Some(String::from("https://tenant.example")));
+    let resolver_msg = ExecuteMsg::UpdateResolver {
+        name: String::from("loft"),
+        new_resolver: mock.clone(),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &resolver_msg, &[])
+        .is_err());
+    let _update = app
+        .execute_contract(tenant.clone(), name_service.clone(), &resolver_msg, &[])
+        .unwrap();
+    let res: ResolveRecordResponse =
+        query(&mut app, name_service.clone(), resolve_msg.clone()).unwrap();
+    assert_eq!(res.address, Some(mock.to_string()));
+    // and the registry can't hand the name over during the lease
+    let transfer_msg = ExecuteMsg::TransferName {
+        name: String::from("loft"),
+        recipient: mock.clone(),
+        new_resolver: None,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &transfer_msg, &[])
+        .is_err());
+
+    // the name returns to the owner's resolver once the lease ends
+    increment_block_time(&mut app, current_time + 3601, 77);
+    let res: ResolveRecordResponse =
+        query(&mut app, name_service.clone(), resolve_msg.clone()).unwrap();
+    assert_eq!(res.address, Some(name_owner.to_string()));
+    // and its own records in place of the tenant's
+    let res: TextResponse = query(&mut app, name_service.clone(), text_query.clone()).unwrap();
+    assert_eq!(res.value, Some(String::from("https://archid.app")));
+    // and renewing settles the lease without giving the name back to the tenant
+    let renew_msg = ExecuteMsg::RenewRegistration {
+        name: String::from("loft"),
+    };
+    let _renew = app
+        .execute_contract(
+            name_owner.clone(),
+            name_service.clone(),
+            &renew_msg,
+            &[Coin {
+                denom: String::from(DENOM),
+                amount: Uint128::from(5000u128),
+            }],
+        )
+        .unwrap();
+    let res: ResolveRecordResponse = query(&mut app, name_service.clone(), resolve_msg).unwrap();
+    assert_eq!(res.address, Some(name_owner.to_string()));
+    let res: TextResponse = query(&mut app, name_service.clone(), text_query).unwrap();
+    assert_eq!(res.value, Some(String::from("https://archid.app")));
+    let res: LeaseResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Lease {
+            name: String::from("loft.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.lease, None);
+    assert!(app
+        .execute_contract(tenant.clone(), name_service.clone(), &resolver_msg, &[])
+        .is_err());
+    let _update = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &resolver_msg, &[])
+        .unwrap();
+}
+
+#[test]
+fn test_internationalised_names() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(50000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+    let payment = [Coin {
+        denom: String::from(DENOM),
+        amount: Uint128::from(5000u128),
+    }];
+
+    // mixed case is folded rather than rejected
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("Alice"),
+    };
+    let _register = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &payment)
+        .unwrap();
+    let res: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("alice.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.address, Some(name_owner.to_string()));
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("alice"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &payment)
+        .is_err());
+
+    // unicode names are stored under their punycode key
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("bücher"),
+    };
+    let _register = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &payment)
+        .unwrap();
+    let res: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("xn--bcher-kva.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.address, Some(name_owner.to_string()));
+    let res: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("Bücher.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.address, Some(name_owner.to_string()));
+    // every name lookup folds its argument the same way
+    let expiration: RecordExpirationResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::RecordExpiration {
+            name: String::from("Bücher.arch"),
+        },
+    )
+    .unwrap();
+    let folded: RecordExpirationResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::RecordExpiration {
+            name: String::from("xn--bcher-kva.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(expiration, folded);
+    let owned: NamesByOwnerResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::NamesByOwner {
+            owner: name_owner.clone(),
+            start_after: None,
+            limit: None,
+            tld: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(owned.names.len(), 2);
+    assert_eq!(owned.names[0].name, "alice.arch");
+    assert_eq!(owned.names[0].display_name, "alice.arch");
+    assert_eq!(owned.names[1].name, "xn--bcher-kva.arch");
+    assert_eq!(owned.names[1].display_name, "bücher.arch");
+
+    // latin mixed with a cyrillic `а` is rejected
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("p\u{430}ypal"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &payment)
+        .is_err());
+
+    // all cyrillic `аре` reads as the ascii `ape`
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("\u{430}\u{440}\u{435}"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &payment)
+        .is_err());
+
+    // punycode that doesn't decode is rejected
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("xn--abc-"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &payment)
+        .is_err());
+
+    // subdomain labels go through the same checks
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("alice"),
+        subdomain: String::from("p\u{430}y"),
+        new_resolver: name_owner.clone(),
+        new_owner: name_owner.clone(),
+        expiration: get_block_time(&mut app) + 3600,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &subdomain_msg, &[])
+        .is_err());
+}
+
+#[test]
+fn test_validation_rules() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(50000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let mut update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config.clone(),
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+    let payment = [Coin {
+        denom: String::from(DENOM),
+        amount: Uint128::from(5000u128),
+    }];
+
+    // 2 character names need a rules update
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("ab"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &payment)
+        .is_err());
+
+    update_config.validation = ValidationRules {
+        name: NameRules {
+            min_length: 2,
+            allow_trailing_hyphen: false,
+            reserved: vec![String::from("admin*")],
+            ..NameRules::default()
+        },
+        subdomain: NameRules {
+            min_length: 1,
+            allowed_chars: String::from("-"),
+            ..NameRules::default()
+        },
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config.clone(),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &update_msg, &[])
+        .is_err());
+    let _config_update = app
+        .execute_contract(owner.clone(), name_service.clone(), &update_msg, &[])
+        .unwrap();
+    let rules: ValidationRules = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ValidationRules {},
+    )
+    .unwrap();
+    assert_eq!(rules, update_config.validation);
+
+    let _register = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &payment)
+        .unwrap();
+    for name in &["abc-", "admin", "administrator"] {
+        let register_msg = ExecuteMsg::Register {
+            name: name.to_string(),
+        };
+        assert!(app
+            .execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &payment)
+            .is_err());
+    }
+
+    // subdomains follow their own rules
+    let expiration = get_block_time(&mut app) + 3600;
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("ab"),
+        subdomain: String::from("x_y"),
+        new_resolver: name_owner.clone(),
+        new_owner: name_owner.clone(),
+        expiration,
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &subdomain_msg, &[])
+        .is_err());
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("ab"),
+        subdomain: String::from("x"),
+        new_resolver: name_owner.clone(),
+        new_owner: name_owner.clone(),
+        expiration,
+    };
+    let _subdomain = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &subdomain_msg, &[])
+        .unwrap();
+
+    // tighter rules only bind new names, existing ones can still be managed
+    let mut tightened = update_config.clone();
+    tightened.validation = ValidationRules::default();
+    let update_msg = ExecuteMsg::UpdateConfig { config: tightened };
+    let _config_update = app
+        .execute_contract(owner.clone(), name_service.clone(), &update_msg, &[])
+        .unwrap();
+    let renew_msg = ExecuteMsg::RenewRegistration {
+        name: String::from("ab"),
+    };
+    let _renew = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &renew_msg, &payment)
+        .unwrap();
+    let fuse_msg = ExecuteMsg::BurnFuses {
+        domain: String::from("ab"),
+        subdomain: String::from("x"),
+        fuses: vec![Fuse::CannotRemove],
+    };
+    let _fuses = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &fuse_msg, &[])
+        .unwrap();
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("cd"),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &payment)
+        .is_err());
+
+    // rules that can't be satisfied are refused
+    update_config.validation.name.max_length = 1;
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    assert!(app
+        .execute_contract(owner.clone(), name_service.clone(), &update_msg, &[])
+        .is_err());
+}
+
+#[test]
+fn test_multiple_tlds() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(50000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let dao_nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    // `.dao` names are cheaper, can be 2 characters and mint on their own collection
+    let settings = Tld {
+        base_cost: Uint128::from(1000u64),
+        base_expiration: 86400,
+        validation: ValidationRules {
+            name: NameRules {
+                min_length: 2,
+                ..NameRules::default()
+            },
+            subdomain: NameRules::default(),
+        },
+        cw721: Some(dao_nft.clone()),
+    };
+    let tld_msg = ExecuteMsg::SetTld {
+        tld: String::from("dao"),
+        settings: settings.clone(),
+    };
+    assert!(app
+        .execute_contract(name_owner.clone(), name_service.clone(), &tld_msg, &[])
+        .is_err());
+    let arch_msg = ExecuteMsg::SetTld {
+        tld: String::from("arch"),
+        settings: settings.clone(),
+    };
+    assert!(app
+        .execute_contract(owner.clone(), name_service.clone(), &arch_msg, &[])
+        .is_err());
+    let _set_tld = app
+        .execute_contract(owner.clone(), name_service.clone(), &tld_msg, &[])
+        .unwrap();
+    let tlds: TldsResponse = query(&mut app, name_service.clone(), QueryMsg::Tlds {}).unwrap();
+    assert_eq!(tlds.tlds.len(), 2);
+    assert_eq!(tlds.tlds[0].tld, "arch");
+    assert_eq!(tlds.tlds[1].tld, "dao");
+    assert_eq!(tlds.tlds[1].settings, settings);
+
+    let dao_payment = [Coin {
+        denom: String::from(DENOM),
+        amount: Uint128::from(1000u128),
+    }];
+    for name in &["ab.dao", "alice.dao"] {
+        let register_msg = ExecuteMsg::Register {
+            name: name.to_string(),
+        };
+        let _register = app
+            .execute_contract(name_owner.clone(), name_service.clone(), &register_msg, &dao_payment)
+            .unwrap();
+    }
+    // bare names still register under `.arch` with its own rules and price
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("ab"),
+    };
+    assert!(app
+        .execute_contract(
+            name_owner.clone(),
+            name_service.clone(),
+            &register_msg,
+            &[Coin {
+                denom: String::from(DENOM),
+                amount: Uint128::from(5000u128),
+            }],
+        )
+        .is_err());
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("alice.arch"),
+    };
+    let _register = app
+        .execute_contract(
+            name_owner.clone(),
+            name_service.clone(),
+            &register_msg,
+            &[Coin {
+                denom: String::from(DENOM),
+                amount: Uint128::from(5000u128),
+            }],
+        )
+        .unwrap();
+
+    // subdomains of `.dao` names are minted on the `.dao` collection
+    let subdomain_msg = ExecuteMsg::RegisterSubdomain {
+        domain: String::from("alice.dao"),
+        subdomain: String::from("pay"),
+        new_resolver: mock.clone(),
+        new_owner: name_owner.clone(),
+        expiration: get_block_time(&mut app) + 3600,
+    };
+    let _subdomain = app
+        .execute_contract(name_owner.clone(), name_service.clone(), &subdomain_msg, &[])
+        .unwrap();
+    let res: ResolveRecordResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::ResolveRecord {
+            name: String::from("pay.alice.dao"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.address, Some(mock.to_string()));
+
+    let owned: NamesByOwnerResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::NamesByOwner {
+            owner: name_owner.clone(),
+            start_after: None,
+            limit: None,
+            tld: Some(String::from("dao")),
+        },
+    )
+    .unwrap();
+    let names: Vec<String> = owned.names.into_iter().map(|n| n.name).collect();
+    assert_eq!(names, vec!["ab.dao", "alice.dao", "pay.alice.dao"]);
+    let owned: NamesByOwnerResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::NamesByOwner {
+            owner: name_owner.clone(),
+            start_after: None,
+            limit: None,
+            tld: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(owned.names.len(), 1);
+    assert_eq!(owned.names[0].name, "alice.arch");
+}
+
+#[test]
+fn test_available() {
+    let mut app = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let wallet = Addr::unchecked("wallet");
+    let name_owner = Addr::unchecked("mintnames");
+    let mock = Addr::unchecked("testtesttest");
+    mint_native(
+        &mut app,
+        name_owner.to_string(),
+        String::from(DENOM),
+        Uint128::from(10000u128),
+    );
+    let name_service = create_name_service(
+        &mut app,
+        owner.clone(),
+        wallet.clone(),
+        mock.clone(),
+        Uint128::from(5000u64),
+        10000000,
+    );
+    let nft = create_cw721(&mut app, &name_service);
+    let update_config = Config {
+        admin: owner.clone(),
+        wallet: wallet.clone(),
+        cw721: nft.clone(),
+        base_cost: Uint128::from(5000u64),
+        base_expiration: 86400,
+        protocol_fee: Decimal::zero(),
+        royalty: Decimal::zero(),
+        sync_subdomain_metadata: true,
+        transfer_policy: TransferPolicy::Keep,
+        validation: ValidationRules {
+            name: NameRules {
+                reserved: vec![String::from("admin*")],
+                ..NameRules::default()
+            },
+            subdomain: NameRules::default(),
+        },
+    };
+    let update_msg = ExecuteMsg::UpdateConfig {
+        config: update_config,
+    };
+    let _config_update =
+        app.execute_contract(owner.clone(), name_service.clone(), &update_msg, &[]);
+
+    let register_msg = ExecuteMsg::Register {
+        name: String::from("taken"),
+    };
+    let _register = app
+        .execute_contract(
+            name_owner.clone(),
+            name_service.clone(),
+            &register_msg,
+            &[Coin {
+                denom: String::from(DENOM),
+                amount: Uint128::from(5000u128),
+            }],
+        )
+        .unwrap();
+    let expiration = get_block_time(&mut app) + 86400;
+
+    let res: AvailableResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Available {
+            name: String::from("Free"),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        AvailableResponse {
+            name: String::from("free.arch"),
+            availability: Availability::Available,
+            price: Some(Uint128::from(5000u128)),
+            period: Some(86400),
+        }
+    );
+
+    let res: AvailableBatchResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::AvailableBatch {
+            names: vec![
+                String::from("taken"),
+                String::from("administrator"),
+                String::from("ab"),
+                String::from("alice.foo"),
+            ],
+        },
+    )
+    .unwrap();
+    assert_eq!(res.names.len(), 4);
+    assert_eq!(res.names[0].availability, Availability::Taken { expiration });
+    assert_eq!(res.names[1].availability, Availability::Reserved);
+    assert!(matches!(res.names[2].availability, Availability::Invalid { .. }));
+    assert_eq!(res.names[2].price, None);
+    // unknown tlds are reported as such rather than as a bad character
+    assert_eq!(res.names[3].name, "alice.foo");
+    assert_eq!(
+        res.names[3].availability,
+        Availability::Invalid {
+            reason: String::from("unknown tld foo")
+        }
+    );
+
+    // expired names can be registered again
+    increment_block_time(&mut app, expiration + 1, 100);
+    let res: AvailableResponse = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::Available {
+            name: String::from("taken.arch"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.availability, Availability::Available);
+
+    let names = (0..31).map(|i| format!("name{}", i)).collect();
+    let res: Result<AvailableBatchResponse, StdError> = query(
+        &mut app,
+        name_service.clone(),
+        QueryMsg::AvailableBatch { names },
+    );
+    assert!(res.is_err());
+}
@@ -1,7 +1,9 @@
 pub mod contract;
 mod error;
+pub mod ibc;
 #[cfg(test)]
 mod integration_test;
+pub mod migrations;
 pub mod msg;
 pub mod read_utils;
 pub mod state;
@@ -0,0 +1,78 @@
+use cosmwasm_std::{DepsMut, Env, StdError, StdResult, Storage};
+use semver::Version;
+
+use crate::error::ContractError;
+use crate::msg::MigrateMsg;
+use crate::read_utils::{name_config, name_owner};
+use crate::state::{
+    may_load_record, migrate_legacy_config, migrate_legacy_records, CONFIG, RESOLVER_OWNERS,
+};
+
+/// A migration step upgrades state written by any version below `version`.
+/// Steps can rewrite `Config` and `NameRecord` layouts, and may read values
+/// that an upgrade introduces from the `MigrateMsg`.
+pub struct MigrationStep {
+    pub version: &'static str,
+    pub run: fn(&mut dyn Storage, &Env, &MigrateMsg) -> Result<(), ContractError>,
+}
+
+/// Registered steps, in ascending version order
+pub const MIGRATIONS: &[MigrationStep] = &[MigrationStep {
+    version: "1.1.0",
+    run: v1_1_0,
+}];
+
+pub fn parse_version(version: &str) -> StdResult<Version> {
+    Version::parse(version)
+        .map_err(|e| StdError::generic_err(format!("Invalid version {}: {}", version, e)))
+}
+
+/// run_migrations applies every step newer than `from` and not newer than `to`
+pub fn run_migrations(
+    storage: &mut dyn Storage,
+    env: &Env,
+    msg: &MigrateMsg,
+    from: &Version,
+    to: &Version,
+) -> Result<Vec<String>, ContractError> {
+    if let Some(c) = &msg.config {
+        c.validate()?;
+    }
+    let mut applied = vec![];
+    for step in MIGRATIONS.iter() {
+        let step_version = parse_version(step.version)?;
+        if &step_version > from && &step_version <= to {
+            (step.run)(storage, env, msg)?;
+            applied.push(step.version.to_string());
+        }
+    }
+    // new config values carried by the upgrade replace the migrated config
+    if let Some(c) = &msg.config {
+        CONFIG.save(storage, c)?;
+    }
+    Ok(applied)
+}
+
+/// migrate_legacy_names moves up to `limit` legacy records and returns how
+/// many were moved. Their resolvers predate the transfer policy, so the
+/// current NFT owner is recorded as the one who set them.
+pub fn migrate_legacy_names(deps: DepsMut, limit: usize) -> StdResult<u64> {
+    let names = migrate_legacy_records(deps.storage, limit)?;
+    for name in names.iter() {
+        let minted = may_load_record(deps.storage, name)?.map_or(false, |r| !r.resolver_only);
+        if minted && !RESOLVER_OWNERS.has(deps.storage, name) {
+            let c = name_config(deps.storage, name)?;
+            let owner = name_owner(&deps.as_ref(), name, &c.cw721)?.owner;
+            let owner = deps.api.addr_validate(&owner)?;
+            RESOLVER_OWNERS.save(deps.storage, name, &owner)?;
+        }
+    }
+    Ok(names.len() as u64)
+}
+
+// 1.1.0 moved state from cosmwasm_storage to cw-storage-plus, name records
+// are transferred in chunks after the steps have run
+fn v1_1_0(storage: &mut dyn Storage, _env: &Env, _msg: &MigrateMsg) -> Result<(), ContractError> {
+    migrate_legacy_config(storage)?;
+    Ok(())
+}
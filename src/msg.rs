@@ -1,6 +1,9 @@
-use crate::state::{Config};
+use crate::state::{
+    default_sync_subdomain_metadata, Config, Fuse, Lease, Listing, ManagerScope, Offer, Registrar,
+    RenewalTerms, Tld, TransferPolicy, ValidationRules,
+};
 use archid_token::{Account, Website};
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Binary, Decimal, Uint128};
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -11,6 +14,16 @@ pub struct InstantiateMsg {
     pub cw721: Addr,
     pub base_cost: Uint128,
     pub base_expiration: u64,
+    #[serde(default)]
+    pub protocol_fee: Decimal,
+    #[serde(default)]
+    pub royalty: Decimal,
+    #[serde(default = "default_sync_subdomain_metadata")]
+    pub sync_subdomain_metadata: bool,
+    #[serde(default)]
+    pub transfer_policy: TransferPolicy,
+    #[serde(default)]
+    pub validation: ValidationRules,
 }
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct MetaDataUpdateMsg {
@@ -58,19 +71,241 @@ pub enum ExecuteMsg {
         name: String,
         metadata_update: MetaDataUpdateMsg,
     },
+    /// Sets a text record (e.g. avatar, email, url), an empty value removes it
+    SetText {
+        name: String,
+        key: String,
+        value: String,
+    },
+    /// Sets the address of a name on another chain, keyed by bech32 prefix
+    /// (e.g. `osmo`) or `eip155:<chain id>`, an empty address removes it
+    SetChainAddress {
+        name: String,
+        chain: String,
+        address: String,
+    },
+    /// Delegates resolution of a name to a contract implementing
+    /// `ResolverQueryMsg`, `None` returns to resolving from the registry
+    SetResolverContract {
+        name: String,
+        contract: Option<Addr>,
+    },
+    /// Resolves every `*.domain.arch` without a record of its own to
+    /// `resolver`, or through `resolver_contract` when set
+    SetWildcard {
+        domain: String,
+        resolver: Addr,
+        resolver_contract: Option<Addr>,
+    },
+    RemoveWildcard {
+        domain: String,
+    },
+    /// Lets anyone claim a direct subdomain of `domain` on the registrar terms
+    OpenRegistrar {
+        domain: String,
+        registrar: Registrar,
+    },
+    CloseRegistrar {
+        domain: String,
+    },
+    /// Registers `subdomain.domain.arch` to the sender through the domain's
+    /// registrar, the resolver defaults to the sender
+    ClaimSubdomain {
+        domain: String,
+        subdomain: String,
+        new_resolver: Option<Addr>,
+    },
+    /// Lets holders of direct subdomains of `domain` renew them with `RenewSubdomain`
+    SetRenewalTerms {
+        domain: String,
+        terms: RenewalTerms,
+    },
+    RemoveRenewalTerms {
+        domain: String,
+    },
+    /// Extends `subdomain.domain.arch` on the parent's renewal terms, only
+    /// callable by the subdomain owner
+    RenewSubdomain {
+        domain: String,
+        subdomain: String,
+    },
+    /// Points a subdomain at `resolver` without minting an NFT, it can be
+    /// minted later with `RegisterSubdomain`
+    SetResolverOnlySubdomain {
+        domain: String,
+        subdomain: String,
+        resolver: Addr,
+        expiration: u64,
+    },
+    /// Burns fuses on `subdomain.domain.arch`, giving up the matching parent
+    /// rights until the subdomain expires
+    BurnFuses {
+        domain: String,
+        subdomain: String,
+        fuses: Vec<Fuse>,
+    },
+    /// Burns subdomains of `domain` minted before it was last registered,
+    /// callable by anyone
+    PruneSubdomains {
+        domain: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lets `manager` act for the NFT owner of `name` within `scopes`, the
+    /// approval lapses at `expires` or once the NFT changes hands
+    ApproveManager {
+        name: String,
+        manager: Addr,
+        scopes: Vec<ManagerScope>,
+        expires: Option<u64>,
+    },
+    RevokeManager {
+        name: String,
+        manager: Addr,
+    },
+    /// Moves the NFT of `name` to `recipient` and points the name at
+    /// `new_resolver`, defaulting to the recipient. The registry must be an
+    /// approved spender of the token.
+    TransferName {
+        name: String,
+        recipient: Addr,
+        new_resolver: Option<Addr>,
+    },
+    /// Offers `name` for sale at a fixed price, the registry must be an
+    /// approved spender of the NFT
+    ListName {
+        name: String,
+        price: Uint128,
+        denom: String,
+        expires: Option<u64>,
+    },
+    CancelListing {
+        name: String,
+    },
+    /// Buys a listed name, paying the listing price. The name resolves to
+    /// `new_resolver`, defaulting to the buyer.
+    BuyName {
+        name: String,
+        new_resolver: Option<Addr>,
+    },
+    /// Escrows the attached funds as an offer on `name` until `expires`,
+    /// replacing and refunding an earlier offer of the sender
+    MakeOffer {
+        name: String,
+        expires: u64,
+    },
+    /// Sells `name` to `bidder` for its offer, the name resolves to the
+    /// bidder. The registry must be an approved spender of the NFT.
+    AcceptOffer {
+        name: String,
+        bidder: Addr,
+    },
+    RejectOffer {
+        name: String,
+        bidder: Addr,
+    },
+    /// Refunds an offer that expired or was rejected
+    WithdrawOffer {
+        name: String,
+    },
+    /// Lends `name` to `tenant` until `expires`, the tenant controls the
+    /// resolver and metadata while the owner keeps the NFT
+    LeaseName {
+        name: String,
+        tenant: Addr,
+        expires: u64,
+    },
+    /// Hands an ended lease back to the owner, the tenant can also end its
+    /// lease early
+    EndLease {
+        name: String,
+    },
+    /// Adds a top level domain next to `.arch` or updates its settings,
+    /// admin only
+    SetTld {
+        tld: String,
+        settings: Tld,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    /// max number of legacy name records moved by this migration call
+    pub limit: Option<u32>,
+    /// replaces the config once the migration steps have run, used to set
+    /// values introduced by the new version
+    pub config: Option<Config>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     ResolveRecord { name: String },
     RecordExpiration { name: String },
-    ResolveAddress { address: Addr },
+    /// Names resolving to `address`, one page at a time
+    ResolveAddress {
+        address: Addr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    NamesByOwner {
+        owner: Addr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+        /// tld whose collection is listed, names sharing the default
+        /// collection are all listed when unset
+        tld: Option<String>,
+    },
+    Text { name: String, key: String },
+    AllTexts {
+        name: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    ResolveForChain { name: String, chain: String },
+    Registrar { domain: String },
+    RenewalTerms { domain: String },
+    /// Fuses burned on an active subdomain
+    Fuses { name: String },
+    /// Direct subdomains of `domain`, ordered by label
+    ListSubdomains {
+        domain: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Managers approved by the current NFT owner of `name`
+    Managers { name: String },
+    /// Listing of `name` if it can currently be bought
+    Listing { name: String },
+    /// Names that can currently be bought, ordered by name
+    Listings {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Active lease of `name`
+    Lease { name: String },
+    /// Offers on `name`, ordered by bidder
+    OffersByName {
+        name: String,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    /// Offers made by `bidder`, ordered by name
+    OffersByBidder {
+        bidder: Addr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
     Config {},
+    /// Naming rules for top level names and subdomains
+    ValidationRules {},
+    /// Every top level domain with its settings, `.arch` first
+    Tlds {},
+    /// Dry-runs `register` for `name`, bare names fall under `.arch`
+    Available { name: String },
+    /// Dry-runs `register` for up to 30 candidate names
+    AvailableBatch { names: Vec<String> },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -87,3 +322,172 @@ pub struct RecordExpirationResponse {
     pub created: u64,
     pub expiration: u64,
 }
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct OwnedName {
+    pub name: String,
+    /// unicode form of `name`, equal to it for ascii names
+    pub display_name: String,
+    pub expiration: u64,
+    pub subdomain: bool,
+    pub expired: bool,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct NamesByOwnerResponse {
+    pub names: Vec<OwnedName>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct TextResponse {
+    pub value: Option<String>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct TextRecord {
+    pub key: String,
+    pub value: String,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AllTextsResponse {
+    pub texts: Vec<TextRecord>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ResolveForChainResponse {
+    pub address: Option<String>,
+    /// true if no record was set and the address was re-encoded from the
+    /// Archway resolver, which only matches chains sharing its coin type (118)
+    pub derived: bool,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct SubdomainEntry {
+    pub label: String,
+    pub name: String,
+    pub resolver: Addr,
+    pub expiration: u64,
+    pub expired: bool,
+    /// false for resolver-only subdomains
+    pub minted: bool,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ListSubdomainsResponse {
+    pub subdomains: Vec<SubdomainEntry>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RegistrarResponse {
+    pub registrar: Option<Registrar>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct FusesResponse {
+    pub fuses: Vec<Fuse>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ManagerInfo {
+    pub manager: Addr,
+    pub scopes: Vec<ManagerScope>,
+    pub expires: Option<u64>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ManagersResponse {
+    pub managers: Vec<ManagerInfo>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ListingResponse {
+    pub listing: Option<Listing>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct TldEntry {
+    pub tld: String,
+    pub settings: Tld,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct TldsResponse {
+    pub tlds: Vec<TldEntry>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ListingEntry {
+    pub name: String,
+    pub listing: Listing,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ListingsResponse {
+    pub listings: Vec<ListingEntry>,
+}
+/// Verdict of a dry-run registration
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Availability {
+    Available,
+    /// registered until `expiration`, it can be registered again afterwards
+    Taken { expiration: u64 },
+    /// matches a reserved pattern of its tld
+    Reserved,
+    /// refused by the naming rules of its tld
+    Invalid { reason: String },
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AvailableResponse {
+    /// fully qualified and normalised name
+    pub name: String,
+    pub availability: Availability,
+    /// cost in `aarch` of one registration period, unset for invalid names
+    pub price: Option<Uint128>,
+    /// length in seconds of one registration period
+    pub period: Option<u64>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AvailableBatchResponse {
+    pub names: Vec<AvailableResponse>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LeaseResponse {
+    pub lease: Option<Lease>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct OfferEntry {
+    pub name: String,
+    pub bidder: Addr,
+    pub offer: Offer,
+    pub expired: bool,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct OffersResponse {
+    pub offers: Vec<OfferEntry>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RenewalTermsResponse {
+    pub terms: Option<RenewalTerms>,
+}
+/// Packets accepted on the resolver IBC channel
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolverPacket {
+    /// Answered with a `ResolveRecordResponse`
+    ResolveRecord { name: String },
+    /// Answered with a `ResolveAddressResponse` holding one page of names,
+    /// continue from the last name returned with `start_after`
+    ResolveAddress {
+        address: Addr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+/// Acknowledgement written for every `ResolverPacket`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolverAck {
+    Result(Binary),
+    Error(String),
+}
+/// Interface implemented by resolver contracts that names can delegate to
+/// with `SetResolverContract`. `name` is always the fully qualified name.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolverQueryMsg {
+    /// Answered with an `AddressResponse`
+    Address { name: String },
+    /// Answered with a `TextResponse`
+    Text { name: String, key: String },
+    /// Answered with an `AddressResponse`
+    ChainAddress { name: String, chain: String },
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AddressResponse {
+    pub address: Option<String>,
+}
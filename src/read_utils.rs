@@ -1,23 +1,47 @@
+use bech32::Variant;
 use cosmwasm_std::{
-    to_binary, Addr, Binary, BlockInfo, Deps, DepsMut, Env, Order, QueryRequest, 
-    Record, StdError, StdResult, WasmQuery,
+    to_binary, Addr, Binary, BlockInfo, Deps, DepsMut, Env, Order, QueryRequest, StdError,
+    StdResult, Storage, Timestamp, WasmQuery,
 };
+use cw_storage_plus::Bound;
+use cw_utils::Expiration;
+use serde::de::DeserializeOwned;
+use unicode_security::{skeleton, MixedScript};
 
 use archid_token::{Extension, Metadata, QueryMsg as Cw721QueryMsg};
-use cw721_updatable::{NftInfoResponse, OwnerOfResponse};
+use cw721_updatable::{NftInfoResponse, OwnerOfResponse, TokensResponse};
 
 use crate::error::ContractError;
-use crate::msg::{ResolveAddressResponse, RecordExpirationResponse, ResolveRecordResponse,};
-use crate::state::{NameRecord, resolver_read};
+use crate::msg::{
+    AddressResponse, AllTextsResponse, Availability, AvailableBatchResponse, AvailableResponse,
+    FusesResponse, LeaseResponse, ListSubdomainsResponse, ListingEntry, ListingResponse,
+    ListingsResponse, ManagerInfo, ManagersResponse, NamesByOwnerResponse, OfferEntry,
+    OffersResponse, OwnedName, RecordExpirationResponse, RegistrarResponse, RenewalTermsResponse,
+    ResolveAddressResponse, ResolveForChainResponse, ResolveRecordResponse, ResolverQueryMsg,
+    SubdomainEntry, TextRecord, TextResponse, TldEntry, TldsResponse,
+};
+use crate::state::{
+    active_lease, chain_addresses, current_epoch, may_load_record, return_leased_resolver,
+    text_records, Config, Listing, ManagerScope, NameRecord, NameRules, Tld, TransferPolicy,
+    ADDRESS_NAMES, BIDDER_OFFERS, CONFIG, FUSES, LISTINGS, MANAGERS, OFFERS, REGISTRARS,
+    RENEWAL_TERMS, RESOLVER_OWNERS, SUBDOMAINS, TLDS, WILDCARDS,
+};
 
-const MIN_NAME_LENGTH: u64 = 3;
-const MAX_NAME_LENGTH: u64 = 64;
-const SUFFIX: &str = ".arch";
+pub const DEFAULT_TLD: &str = "arch";
+const PUNYCODE_PREFIX: &str = "xn--";
+const EVM_CHAIN_PREFIX: &str = "eip155:";
+const MAX_BECH32_PREFIX_LENGTH: usize = 16;
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
 pub fn query_name_owner(
     id: &str,
     cw721: &Addr,
     deps: &DepsMut,
 ) -> Result<OwnerOfResponse, StdError> {
+    name_owner(&deps.as_ref(), id, cw721)
+}
+
+pub fn name_owner(deps: &Deps, id: &str, cw721: &Addr) -> StdResult<OwnerOfResponse> {
     let query_msg: archid_token::QueryMsg<Extension> = Cw721QueryMsg::OwnerOf {
         token_id: id.to_owned(),
         include_expired: None,
@@ -30,13 +54,270 @@ pub fn query_name_owner(
     Ok(res)
 }
 
+/// is_authorized tells whether `sender` is the NFT `owner` of `name` or an
+/// active manager of it for `scope`
+pub fn is_authorized(
+    deps: &Deps,
+    block: &BlockInfo,
+    name: &str,
+    owner: &str,
+    sender: &Addr,
+    scope: ManagerScope,
+) -> StdResult<bool> {
+    if sender == owner {
+        return Ok(true);
+    }
+    Ok(MANAGERS
+        .may_load(deps.storage, (name, sender))?
+        .map_or(false, |m| {
+            m.is_active(owner, block) && m.scopes.contains(&scope)
+        }))
+}
+
+pub fn query_managers(deps: Deps, env: Env, name: String) -> StdResult<Binary> {
+    let mut managers = vec![];
+    // resolver-only subdomains have no NFT owner to approve managers
+    if active_record(&deps, &env, &name)?.map_or(false, |r| !r.resolver_only) {
+        let c = name_config(deps.storage, &name)?;
+        let owner = name_owner(&deps, &name, &c.cw721)?.owner;
+        for item in MANAGERS
+            .prefix(name.as_str())
+            .range(deps.storage, None, None, Order::Ascending)
+        {
+            let (manager, approval) = item?;
+            if approval.is_active(&owner, &env.block) {
+                managers.push(ManagerInfo {
+                    manager,
+                    scopes: approval.scopes,
+                    expires: approval.expires,
+                });
+            }
+        }
+    }
+    to_binary(&ManagersResponse { managers })
+}
+
+/// transferred_to returns the new NFT owner of `name` if the NFT changed
+/// hands since its resolver was last set and the transfer policy applies
+pub fn transferred_to(deps: &Deps, name: &str, record: &NameRecord) -> StdResult<Option<String>> {
+    let c = name_config(deps.storage, name)?;
+    if c.transfer_policy == TransferPolicy::Keep || record.resolver_only {
+        return Ok(None);
+    }
+    // names whose resolver predates the policy have no owner to compare
+    let setter = match RESOLVER_OWNERS.may_load(deps.storage, name)? {
+        Some(setter) => setter,
+        None => return Ok(None),
+    };
+    let owner = name_owner(deps, name, &c.cw721)?.owner;
+    Ok(Some(owner).filter(|owner| setter != *owner))
+}
+
+/// active_listing returns the listing of `name` if it can be bought, listings
+/// lapse when they expire, when the name expires or once the seller no
+/// longer owns the NFT
+pub fn active_listing(deps: &Deps, env: &Env, name: &str) -> StdResult<Option<Listing>> {
+    let listing = match LISTINGS.may_load(deps.storage, name)? {
+        Some(listing) if !listing.is_expired(&env.block) => listing,
+        _ => return Ok(None),
+    };
+    match active_record(deps, env, name)? {
+        Some(record)
+            if !record.resolver_only && !ancestors_expired(deps.storage, name, &env.block)? => {}
+        _ => return Ok(None),
+    }
+    let c = name_config(deps.storage, name)?;
+    let owner = name_owner(deps, name, &c.cw721)?.owner;
+    Ok(Some(listing).filter(|l| l.seller == owner))
+}
+
+pub fn query_listing(deps: Deps, env: Env, name: String) -> StdResult<Binary> {
+    let listing = active_listing(&deps, &env, &name)?;
+    to_binary(&ListingResponse { listing })
+}
+
+pub fn query_listings(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+    // lapsed listings are skipped, so a page can hold fewer than `limit` entries
+    let names = LISTINGS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<String>>>()?;
+    let mut listings = vec![];
+    for name in names.into_iter() {
+        if let Some(listing) = active_listing(&deps, &env, &name)? {
+            listings.push(ListingEntry { name, listing });
+        }
+    }
+    to_binary(&ListingsResponse { listings })
+}
+
+pub fn query_offers_by_name(
+    deps: Deps,
+    env: Env,
+    name: String,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
+    let offers = OFFERS
+        .prefix(name.as_str())
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(bidder, offer)| OfferEntry {
+                name: name.clone(),
+                bidder,
+                expired: offer.is_expired(&env.block),
+                offer,
+            })
+        })
+        .collect::<StdResult<Vec<OfferEntry>>>()?;
+    to_binary(&OffersResponse { offers })
+}
+
+pub fn query_offers_by_bidder(
+    deps: Deps,
+    env: Env,
+    bidder: Addr,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+    let names = BIDDER_OFFERS
+        .prefix(&bidder)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<String>>>()?;
+    let mut offers = vec![];
+    for name in names.into_iter() {
+        let offer = OFFERS.load(deps.storage, (name.as_str(), &bidder))?;
+        offers.push(OfferEntry {
+            name,
+            bidder: bidder.clone(),
+            expired: offer.is_expired(&env.block),
+            offer,
+        });
+    }
+    to_binary(&OffersResponse { offers })
+}
+
+pub fn query_lease(deps: Deps, env: Env, name: String) -> StdResult<Binary> {
+    let lease = active_lease(deps.storage, &env.block, &name)?;
+    to_binary(&LeaseResponse { lease })
+}
+
+pub fn query_available(deps: Deps, env: Env, name: String) -> StdResult<Binary> {
+    let tlds = tld_names(deps.storage)?;
+    to_binary(&availability(&deps, &env, &tlds, name)?)
+}
+
+pub fn query_available_batch(deps: Deps, env: Env, names: Vec<String>) -> StdResult<Binary> {
+    if names.len() > MAX_LIMIT as usize {
+        return Err(StdError::generic_err(format!(
+            "at most {} names per batch",
+            MAX_LIMIT
+        )));
+    }
+    let tlds = tld_names(deps.storage)?;
+    let names = names
+        .into_iter()
+        .map(|name| availability(&deps, &env, &tlds, name))
+        .collect::<StdResult<Vec<_>>>()?;
+    to_binary(&AvailableBatchResponse { names })
+}
+
+/// availability runs the checks of `execute_register` on `name` without
+/// registering it
+fn availability(
+    deps: &Deps,
+    env: &Env,
+    tlds: &[String],
+    name: String,
+) -> StdResult<AvailableResponse> {
+    // `format_name` would append the default tld to an unknown one
+    let name = normalize_name(&name);
+    let unknown_tld = match name.rsplit_once('.') {
+        Some((_, tld)) if !tlds.iter().any(|t| t == tld) => Some(tld.to_string()),
+        _ => None,
+    };
+    if let Some(tld) = unknown_tld {
+        return Ok(AvailableResponse {
+            name,
+            availability: Availability::Invalid {
+                reason: format!("unknown tld {}", tld),
+            },
+            price: None,
+            period: None,
+        });
+    }
+    let name = format_name(tlds, name);
+    let availability = match validate_name(deps.storage, &name) {
+        Ok(()) => Availability::Available,
+        Err(ContractError::ReservedName { .. }) => Availability::Reserved,
+        Err(err) => Availability::Invalid {
+            reason: err.to_string(),
+        },
+    };
+    if let Availability::Invalid { .. } = availability {
+        return Ok(AvailableResponse {
+            name,
+            availability,
+            price: None,
+            period: None,
+        });
+    }
+    let c = name_config(deps.storage, &name)?;
+    let availability = match may_load_record(deps.storage, &name)? {
+        Some(record) if !record.is_expired(&env.block) => Availability::Taken {
+            expiration: record.expiration,
+        },
+        _ => availability,
+    };
+    Ok(AvailableResponse {
+        name,
+        availability,
+        price: Some(c.base_cost),
+        period: Some(c.base_expiration),
+    })
+}
+
 pub fn query_resolver(deps: Deps, env: Env, name: String) -> StdResult<Binary> {
-    let key = name.as_bytes();
-    let curr = (resolver_read(deps.storage).may_load(key)?).unwrap();
+    // stale subdomains of a previous parent registration count as missing
+    let (curr, new_owner) = match may_load_record(deps.storage, &name)? {
+        Some(mut record) if !is_stale(deps.storage, &name, &record)? => {
+            return_leased_resolver(deps.storage, &env.block, &name, &mut record)?;
+            let new_owner = transferred_to(&deps, &name, &record)?;
+            (record, new_owner)
+        }
+        _ => (
+            wildcard_record(&deps, &name)?.ok_or_else(|| StdError::not_found("NameRecord"))?,
+            None,
+        ),
+    };
 
-    let address = match curr.is_expired(&env.block) {
-        true => None,
-        false => Some(String::from(&curr.resolver)),
+    // a subdomain stops resolving as soon as any of its ancestors expires
+    let expired =
+        curr.is_expired(&env.block) || ancestors_expired(deps.storage, &name, &env.block)?;
+    let policy = CONFIG.load(deps.storage)?.transfer_policy;
+    let address = match (expired, new_owner, &curr.resolver_contract) {
+        (true, _, _) => None,
+        (false, Some(owner), _) if policy == TransferPolicy::ResetToOwner => Some(owner),
+        (false, Some(_), _) => None,
+        (false, None, Some(contract)) => {
+            let msg = ResolverQueryMsg::Address { name };
+            let resp: AddressResponse = query_resolver_contract(&deps, contract, &msg)?;
+            resp.address
+        }
+        (false, None, None) => Some(String::from(&curr.resolver)),
     };
 
     let resp = ResolveRecordResponse {
@@ -46,9 +327,32 @@ pub fn query_resolver(deps: Deps, env: Env, name: String) -> StdResult<Binary> {
     to_binary(&resp)
 }
 
+/// wildcard_record answers a missing `subdomain.domain.arch` with the
+/// wildcard of its parent, expiring with the parent registration
+pub fn wildcard_record(deps: &Deps, name: &str) -> StdResult<Option<NameRecord>> {
+    let parent = match get_subdomain_prefix(name.to_string()) {
+        Some(components) => components[1].clone(),
+        None => return Ok(None),
+    };
+    let wildcard = match WILDCARDS.may_load(deps.storage, &parent)? {
+        Some(wildcard) => wildcard,
+        None => return Ok(None),
+    };
+    let parent_epoch = current_epoch(deps.storage, &parent)?;
+    Ok(
+        may_load_record(deps.storage, &parent)?.map(|domain| NameRecord {
+            resolver: wildcard.resolver,
+            created: domain.created,
+            expiration: domain.expiration,
+            resolver_contract: wildcard.resolver_contract,
+            parent_epoch,
+            resolver_only: false,
+        }),
+    )
+}
+
 pub fn query_resolver_expiration(deps: Deps, _env: Env, name: String) -> StdResult<Binary> {
-    let key = name.as_bytes();
-    let curr = (resolver_read(deps.storage).may_load(key)?).unwrap();
+    let curr = (may_load_record(deps.storage, &name)?).unwrap();
     let resp = RecordExpirationResponse {
         created: curr.created,
         expiration: curr.expiration,
@@ -56,35 +360,275 @@ pub fn query_resolver_expiration(deps: Deps, _env: Env, name: String) -> StdResu
     to_binary(&resp)
 }
 
-pub fn query_resolver_address(deps: Deps, env: Env, address: Addr) -> StdResult<Binary> {
-    let curr: StdResult<Vec<Record<NameRecord>>> = 
-        resolver_read(deps.storage)
-            .range(None, None, Order::Ascending)
-            .collect();
+/// resolved_names pages through the names indexed under `address` and keeps
+/// those that currently resolve to it, so a page can hold fewer than `limit`
+/// names. Records still in the legacy bucket are indexed once migrated.
+fn resolved_names(
+    deps: &Deps,
+    env: &Env,
+    address: &Addr,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<String>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+    let names = ADDRESS_NAMES
+        .prefix(address)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<String>>>()?;
+    let mut resolved = vec![];
+    for name in names.into_iter() {
+        match active_record(deps, env, &name)? {
+            // the resolver no longer applies once the NFT changed hands
+            Some(record)
+                if record.resolver == *address
+                    && transferred_to(deps, &name, &record)?.is_none() =>
+            {
+                resolved.push(name)
+            }
+            _ => {}
+        }
+    }
+    Ok(resolved)
+}
 
-    let records = curr.unwrap();
-    
-    let names = records
-        .into_iter()
-        .filter(|(_i, record)| record.resolver == address)
-        .collect::<Vec<Record<NameRecord>>>();
-    
-    let unexpired_names = names
-        .into_iter()
-        .filter(|(_i, record)| !record.is_expired(&env.block))
-        .collect::<Vec<Record<NameRecord>>>();
+pub fn query_resolver_address(
+    deps: Deps,
+    env: Env,
+    address: Addr,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let names = resolved_names(&deps, &env, &address, start_after, limit)?;
+    let resp = ResolveAddressResponse { names: Some(names) };
+    to_binary(&resp)
+}
+
+pub fn query_names_by_owner(
+    deps: Deps,
+    env: Env,
+    owner: Addr,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    tld: Option<String>,
+) -> StdResult<Binary> {
+    let c = name_config(deps.storage, tld.as_deref().unwrap_or(DEFAULT_TLD))?;
+    let query_msg: archid_token::QueryMsg<Extension> = Cw721QueryMsg::Tokens {
+        owner: owner.to_string(),
+        start_after,
+        limit,
+    };
+    let req = QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: c.cw721.to_string(),
+        msg: to_binary(&query_msg)?,
+    });
+    let tokens: TokensResponse = deps.querier.query(&req)?;
+
+    let mut names = vec![];
+    for token_id in tokens.tokens.into_iter() {
+        // tokens minted outside the registry have no record to report
+        if let Some(record) = may_load_record(deps.storage, &token_id)? {
+            names.push(OwnedName {
+                subdomain: get_subdomain_prefix(token_id.clone()).is_some(),
+                expired: record.is_expired(&env.block)
+                    || is_stale(deps.storage, &token_id, &record)?
+                    || ancestors_expired(deps.storage, &token_id, &env.block)?,
+                expiration: record.expiration,
+                display_name: display_name(&token_id),
+                name: token_id,
+            });
+        }
+    }
+
+    let resp = NamesByOwnerResponse { names };
+    to_binary(&resp)
+}
+
+pub fn query_resolver_contract<T: DeserializeOwned>(
+    deps: &Deps,
+    contract: &Addr,
+    msg: &ResolverQueryMsg,
+) -> StdResult<T> {
+    let req = QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: contract.to_string(),
+        msg: to_binary(msg)?,
+    });
+    deps.querier.query(&req)
+}
 
-    let mut output_names = vec![];
-    for (key, _record) in unexpired_names.into_iter() {
-        output_names.push(String::from_utf8(key).unwrap());
+// records of expired names are hidden until the name is renewed or re-registered,
+// names of ended leases point back at the owner's resolver
+fn active_record(deps: &Deps, env: &Env, name: &str) -> StdResult<Option<NameRecord>> {
+    match may_load_record(deps.storage, name)? {
+        Some(mut record)
+            if !record.is_expired(&env.block) && !is_stale(deps.storage, name, &record)? =>
+        {
+            return_leased_resolver(deps.storage, &env.block, name, &mut record)?;
+            Ok(Some(record))
+        }
+        _ => Ok(None),
     }
+}
 
-    let resp = ResolveAddressResponse {
-        names: Some(output_names.clone()),
+// texts set for a previous owner are dropped under both transfer policies
+pub fn query_text(deps: Deps, env: Env, name: String, key: String) -> StdResult<Binary> {
+    let value = match active_record(&deps, &env, &name)? {
+        Some(record) if transferred_to(&deps, &name, &record)?.is_some() => None,
+        Some(NameRecord {
+            resolver_contract: Some(contract),
+            ..
+        }) => {
+            let msg = ResolverQueryMsg::Text { name, key };
+            let resp: TextResponse = query_resolver_contract(&deps, &contract, &msg)?;
+            resp.value
+        }
+        Some(_) => text_records(deps.storage, &env.block, &name)?
+            .may_load(deps.storage, (name.as_str(), key.as_str()))?,
+        None => None,
     };
+    to_binary(&TextResponse { value })
+}
+
+pub fn query_all_texts(
+    deps: Deps,
+    env: Env,
+    name: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let visible = match active_record(&deps, &env, &name)? {
+        Some(record) => transferred_to(&deps, &name, &record)?.is_none(),
+        None => false,
+    };
+    let mut texts = vec![];
+    if visible {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.as_deref().map(Bound::exclusive);
+        texts = text_records(deps.storage, &env.block, &name)?
+            .prefix(name.as_str())
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(key, value)| TextRecord { key, value }))
+            .collect::<StdResult<Vec<TextRecord>>>()?;
+    }
+    to_binary(&AllTextsResponse { texts })
+}
+
+// a registrar is only reported while its domain can still mint subdomains
+pub fn query_registrar(deps: Deps, env: Env, domain: String) -> StdResult<Binary> {
+    let registrar = match active_record(&deps, &env, &domain)? {
+        Some(_) => REGISTRARS.may_load(deps.storage, &domain)?,
+        None => None,
+    };
+    to_binary(&RegistrarResponse { registrar })
+}
+
+pub fn query_renewal_terms(deps: Deps, env: Env, domain: String) -> StdResult<Binary> {
+    let terms = match active_record(&deps, &env, &domain)? {
+        Some(_) => RENEWAL_TERMS.may_load(deps.storage, &domain)?,
+        None => None,
+    };
+    to_binary(&RenewalTermsResponse { terms })
+}
+
+pub fn query_fuses(deps: Deps, env: Env, name: String) -> StdResult<Binary> {
+    let fuses = match active_record(&deps, &env, &name)? {
+        Some(_) => FUSES.may_load(deps.storage, &name)?.unwrap_or_default(),
+        None => vec![],
+    };
+    to_binary(&FusesResponse { fuses })
+}
+
+pub fn query_list_subdomains(
+    deps: Deps,
+    env: Env,
+    domain: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+    let labels = SUBDOMAINS
+        .prefix(domain.as_str())
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<String>>>()?;
+
+    let mut subdomains = vec![];
+    for label in labels.into_iter() {
+        let name = format!("{}.{}", label, domain);
+        if let Some(mut record) = may_load_record(deps.storage, &name)? {
+            return_leased_resolver(deps.storage, &env.block, &name, &mut record)?;
+            subdomains.push(SubdomainEntry {
+                expired: record.is_expired(&env.block)
+                    || is_stale(deps.storage, &name, &record)?
+                    || ancestors_expired(deps.storage, &name, &env.block)?,
+                expiration: record.expiration,
+                minted: !record.resolver_only,
+                resolver: record.resolver,
+                label,
+                name,
+            });
+        }
+    }
+    to_binary(&ListSubdomainsResponse { subdomains })
+}
+
+pub fn query_resolve_for_chain(
+    deps: Deps,
+    env: Env,
+    name: String,
+    chain: String,
+) -> StdResult<Binary> {
+    let mut resp = ResolveForChainResponse {
+        address: None,
+        derived: false,
+    };
+    if let Some(record) = active_record(&deps, &env, &name)? {
+        let new_owner = transferred_to(&deps, &name, &record)?;
+        let policy = CONFIG.load(deps.storage)?.transfer_policy;
+        match (new_owner, &record.resolver_contract) {
+            // addresses set for a previous owner are dropped, at most the
+            // new owner is derived under the requested prefix
+            (Some(owner), _) => {
+                if policy == TransferPolicy::ResetToOwner && is_bech32_prefix(&chain) {
+                    resp.address = derive_address(&owner, &chain);
+                    resp.derived = resp.address.is_some();
+                }
+            }
+            (None, Some(contract)) => {
+                let msg = ResolverQueryMsg::ChainAddress {
+                    name: name.clone(),
+                    chain: chain.clone(),
+                };
+                let delegated: AddressResponse = query_resolver_contract(&deps, contract, &msg)?;
+                resp.address = delegated.address;
+            }
+            (None, None) => {
+                let stored = chain_addresses(deps.storage, &env.block, &name)?
+                    .may_load(deps.storage, (name.as_str(), chain.as_str()))?;
+                match stored {
+                    Some(address) => resp.address = Some(address),
+                    None if is_bech32_prefix(&chain) => {
+                        // fall back to the archway resolver under the requested prefix
+                        resp.address = derive_address(record.resolver.as_str(), &chain);
+                        resp.derived = resp.address.is_some();
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
     to_binary(&resp)
 }
 
+// derive_address re-encodes an archway address under another bech32 prefix
+fn derive_address(address: &str, chain: &str) -> Option<String> {
+    let (_, data, variant) = bech32::decode(address).ok()?;
+    bech32::encode(chain, data, variant).ok()
+}
+
 pub fn query_current_metadata(
     id: &str,
     cw721: &Addr,
@@ -100,86 +644,262 @@ pub fn query_current_metadata(
     let res: NftInfoResponse<Metadata> = deps.querier.query(&req)?;
     Ok(res.extension)
 }
-fn invalid_char(c: char) -> bool {
-    let is_valid = c.is_ascii_digit() || c.is_ascii_lowercase() || (c == '-' || c == '_');
-    !is_valid
+
+fn is_bech32_prefix(chain: &str) -> bool {
+    !chain.is_empty()
+        && chain.len() <= MAX_BECH32_PREFIX_LENGTH
+        && chain
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+}
+
+/// validate_chain_address returns an error unless `address` is a well formed
+/// address for `chain`, a bech32 prefix or an `eip155:<chain id>` identifier
+pub fn validate_chain_address(chain: &str, address: &str) -> Result<(), ContractError> {
+    let invalid_address = ContractError::InvalidChainAddress {
+        chain: chain.to_string(),
+        address: address.to_string(),
+    };
+    if let Some(chain_id) = chain.strip_prefix(EVM_CHAIN_PREFIX) {
+        if chain_id.is_empty() || !chain_id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(ContractError::InvalidChain {
+                chain: chain.to_string(),
+            });
+        }
+        return match address.strip_prefix("0x") {
+            Some(hex) if hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()) => Ok(()),
+            _ => Err(invalid_address),
+        };
+    }
+    if !is_bech32_prefix(chain) {
+        return Err(ContractError::InvalidChain {
+            chain: chain.to_string(),
+        });
+    }
+    match bech32::decode(address) {
+        Ok((hrp, _, Variant::Bech32)) if hrp == chain && address == address.to_lowercase() => {
+            Ok(())
+        }
+        _ => Err(invalid_address),
+    }
 }
 
-pub fn is_expired(deps: &DepsMut, key: &[u8], block: &BlockInfo) -> bool {
-    let r = resolver_read(deps.storage).may_load(key).unwrap();
+pub fn is_expired(deps: &DepsMut, name: &str, block: &BlockInfo) -> bool {
+    let r = may_load_record(deps.storage, name).unwrap();
     match r.is_some() {
-        true => r.unwrap().is_expired(block),
+        true => {
+            let record = r.unwrap();
+            record.is_expired(block) || is_stale(deps.storage, name, &record).unwrap()
+        }
         _ => true,
     }
 }
 
-/// validate_name returns an error if the name is invalid
-/// (we require 3-64 lowercase ascii letters, numbers, or . - _)
-pub fn validate_name(name: &str) -> Result<(), ContractError> {
-    let length = name.len() as u64;
-    let suffix_index = length as usize - SUFFIX.len();
-    let body = &name[0..suffix_index];
-    if (body.len() as u64) < MIN_NAME_LENGTH {
-        Err(ContractError::NameTooShort {
-            length,
-            min_length: MIN_NAME_LENGTH,
-        })
-    } else if (body.len() as u64) > MAX_NAME_LENGTH {
-        Err(ContractError::NameTooLong {
-            length,
-            max_length: MAX_NAME_LENGTH,
-        })
-    } else {
-        match body.find(invalid_char) {
-            None => Ok(()),
-            Some(bytepos_invalid_char_start) => {
-                let c = name[bytepos_invalid_char_start..].chars().next().unwrap();
-                Err(ContractError::InvalidCharacter { c })
-            }
-        }
+/// is_stale tells whether a subdomain record belongs to an earlier
+/// registration of its parent, stale records resolve to nothing and can be pruned
+pub fn is_stale(storage: &dyn Storage, name: &str, record: &NameRecord) -> StdResult<bool> {
+    match get_parent_name(name) {
+        Some(parent) => Ok(record.parent_epoch != current_epoch(storage, &parent)?),
+        None => Ok(false),
     }
 }
-pub fn validate_subdomain(name: &str) -> Result<(), ContractError> {
-    let length = name.len() as u64;
-    if (name.len() as u64) < MIN_NAME_LENGTH {
-        Err(ContractError::NameTooShort {
+
+/// validate_name returns an error unless the body of a top level name
+/// follows the naming rules of its tld, unicode names arrive here in their
+/// punycode form
+pub fn validate_name(storage: &dyn Storage, name: &str) -> Result<(), ContractError> {
+    let rules = name_config(storage, name)?.validation;
+    validate_label(&get_name_body(name.to_string()), &rules.name)
+}
+/// validate_subdomain returns an error unless a subdomain label of `domain`
+/// follows the naming rules of its tld
+pub fn validate_subdomain(
+    storage: &dyn Storage,
+    domain: &str,
+    label: &str,
+) -> Result<(), ContractError> {
+    let rules = name_config(storage, domain)?.validation;
+    validate_label(label, &rules.subdomain)
+}
+fn validate_label(label: &str, rules: &NameRules) -> Result<(), ContractError> {
+    let length = label.len() as u64;
+    if length < rules.min_length {
+        return Err(ContractError::NameTooShort {
             length,
-            min_length: MIN_NAME_LENGTH,
-        })
-    } else if (name.len() as u64) > MAX_NAME_LENGTH {
-        Err(ContractError::NameTooLong {
+            min_length: rules.min_length,
+        });
+    }
+    if length > rules.max_length {
+        return Err(ContractError::NameTooLong {
             length,
-            max_length: MAX_NAME_LENGTH,
-        })
-    } else {
-        match name.find(invalid_char) {
-            None => Ok(()),
-            Some(bytepos_invalid_char_start) => {
-                let c = name[bytepos_invalid_char_start..].chars().next().unwrap();
-                Err(ContractError::InvalidCharacter { c })
-            }
+            max_length: rules.max_length,
+        });
+    }
+    if let Some(c) = label.chars().find(|c| !rules.allows(*c)) {
+        return Err(ContractError::InvalidCharacter { c });
+    }
+    if (!rules.allow_leading_hyphen && label.starts_with('-'))
+        || (!rules.allow_trailing_hyphen && label.ends_with('-'))
+    {
+        return Err(ContractError::InvalidHyphen {
+            name: label.to_string(),
+        });
+    }
+    if rules.reserved.iter().any(|p| matches_pattern(p, label)) {
+        return Err(ContractError::ReservedName {
+            name: label.to_string(),
+        });
+    }
+    validate_idn_label(label)
+}
+/// matches_pattern matches a label against a pattern where `*` stands for
+/// any run of characters
+fn matches_pattern(pattern: &str, label: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if parts.len() == 1 {
+        return pattern == label;
+    }
+    if label.len() < first.len() + last.len() || !label.starts_with(first) || !label.ends_with(last)
+    {
+        return false;
+    }
+    let mut rest = &label[first.len()..label.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
         }
     }
+    true
+}
+/// validate_idn_label checks a punycode label decodes to its canonical
+/// form, uses a single script and can't pass for an ascii name
+fn validate_idn_label(label: &str) -> Result<(), ContractError> {
+    if !label.starts_with(PUNYCODE_PREFIX) {
+        return Ok(());
+    }
+    let (unicode, result) = idna::domain_to_unicode(label);
+    if result.is_err() || unicode.is_ascii() || normalize_name(&unicode) != label {
+        return Err(ContractError::InvalidIdn {
+            name: label.to_string(),
+        });
+    }
+    if !unicode.as_str().is_single_script() {
+        return Err(ContractError::MixedScriptName { name: unicode });
+    }
+    if skeleton(&unicode).all(|c| c.is_ascii()) {
+        return Err(ContractError::ConfusableName { name: unicode });
+    }
+    Ok(())
+}
+/// normalize_name maps a name to its UTS-46 ascii form, e.g. `Alice` ->
+/// `alice` and `bücher` -> `xn--bcher-kva`, names that can't be mapped are
+/// left as is for validation to reject
+pub fn normalize_name(name: &str) -> String {
+    idna::Config::default()
+        .to_ascii(name)
+        .unwrap_or_else(|_| name.to_string())
+}
+/// display_name returns the unicode form of a stored name
+pub fn display_name(name: &str) -> String {
+    idna::domain_to_unicode(name).0
+}
+/// format_name normalises a name and qualifies it with the default tld
+/// unless it already ends with one of `tlds`, e.g. `alice` -> `alice.arch`
+/// while `alice.dao` is kept once `dao` was added
+pub fn format_name(tlds: &[String], name: String) -> String {
+    let name = normalize_name(&name);
+    match name.rsplit_once('.') {
+        Some((_, tld)) if tlds.iter().any(|t| t == tld) => name,
+        _ => format!("{}.{}", name, DEFAULT_TLD),
+    }
 }
-pub fn format_name(name: String) -> String {
-    let domain_route = format!("{}{}", name, String::from(SUFFIX));
-    domain_route
+/// get_tld returns the top level domain of a fully qualified name
+pub fn get_tld(name: &str) -> &str {
+    name.rsplit_once('.').map_or(name, |(_, tld)| tld)
 }
+/// get_name_body strips the tld off a fully qualified name
 pub fn get_name_body(name: String) -> String {
-    let length = name.len() as u64;
-    let suffix_index = length as usize - SUFFIX.len();
-    let body = &name[0..suffix_index];
-    String::from(body)
+    match name.rsplit_once('.') {
+        Some((body, _)) => String::from(body),
+        None => name,
+    }
 }
+/// get_subdomain_prefix splits a subdomain of any depth into its first
+/// label and its fully qualified parent, e.g. `a.b.c.dao` -> `["a", "b.c.dao"]`
 pub fn get_subdomain_prefix(name: String) -> Option<Vec<String>> {
-    let body = get_name_body(name);
-    let components: Vec<_> = body.split('.').collect();
-    match components.len() {
-        1 => None,
-        2 => Some(vec![
-            String::from(components[0]),
-            String::from(components[1]),
-        ]),
-        _ => None,
+    let (label, parent) = name.split_once('.')?;
+    match parent.contains('.') {
+        true => Some(vec![String::from(label), String::from(parent)]),
+        false => None,
+    }
+}
+/// get_parent_name returns the fully qualified parent of a subdomain
+pub fn get_parent_name(name: &str) -> Option<String> {
+    get_subdomain_prefix(name.to_string()).map(|components| components[1].clone())
+}
+/// tld_names lists the default tld and every tld added by the admin
+pub fn tld_names(storage: &dyn Storage) -> StdResult<Vec<String>> {
+    let mut tlds = vec![String::from(DEFAULT_TLD)];
+    for tld in TLDS.keys(storage, None, None, Order::Ascending) {
+        tlds.push(tld?);
+    }
+    Ok(tlds)
+}
+/// name_config returns the config with the pricing, expiration, naming rules
+/// and collection of `name`'s tld applied
+pub fn name_config(storage: &dyn Storage, name: &str) -> StdResult<Config> {
+    let mut c = CONFIG.load(storage)?;
+    let tld = get_tld(name);
+    if tld != DEFAULT_TLD {
+        let settings = TLDS.load(storage, tld)?;
+        c.base_cost = settings.base_cost;
+        c.base_expiration = settings.base_expiration;
+        c.validation = settings.validation;
+        if let Some(cw721) = settings.cw721 {
+            c.cw721 = cw721;
+        }
+    }
+    Ok(c)
+}
+pub fn query_tlds(deps: Deps, _env: Env) -> StdResult<Binary> {
+    let c = CONFIG.load(deps.storage)?;
+    let mut tlds = vec![TldEntry {
+        tld: String::from(DEFAULT_TLD),
+        settings: Tld {
+            base_cost: c.base_cost,
+            base_expiration: c.base_expiration,
+            validation: c.validation,
+            cw721: Some(c.cw721),
+        },
+    }];
+    for entry in TLDS.range(deps.storage, None, None, Order::Ascending) {
+        let (tld, settings) = entry?;
+        tlds.push(TldEntry { tld, settings });
+    }
+    to_binary(&TldsResponse { tlds })
+}
+/// ancestors_expiration returns the earliest expiration among the ancestors
+/// of `name`, `None` for top level names. Missing or stale ancestors count as expired.
+pub fn ancestors_expiration(storage: &dyn Storage, name: &str) -> StdResult<Option<u64>> {
+    let mut expiration: Option<u64> = None;
+    let mut parent = get_parent_name(name);
+    while let Some(p) = parent {
+        let parent_expiration = match may_load_record(storage, &p)? {
+            Some(r) if !is_stale(storage, &p, &r)? => r.expiration,
+            _ => 0,
+        };
+        expiration = Some(expiration.map_or(parent_expiration, |e| e.min(parent_expiration)));
+        parent = get_parent_name(&p);
     }
+    Ok(expiration)
+}
+pub fn ancestors_expired(storage: &dyn Storage, name: &str, block: &BlockInfo) -> StdResult<bool> {
+    Ok(match ancestors_expiration(storage, name)? {
+        Some(expiration) => {
+            Expiration::AtTime(Timestamp::from_seconds(expiration)).is_expired(block)
+        }
+        None => false,
+    })
 }
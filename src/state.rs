@@ -1,16 +1,61 @@
-use cosmwasm_std::{Addr, BlockInfo, Storage, Timestamp, Uint128};
-use cosmwasm_storage::{
-    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
-    Singleton,
+use cosmwasm_std::{
+    Addr, BlockInfo, Decimal, Empty, Order, StdError, StdResult, Storage, Timestamp, Uint128,
 };
+use cosmwasm_storage::{bucket, bucket_read, singleton, singleton_read};
+use cw_storage_plus::{Item, Map};
 use cw_utils::Expiration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+// keys used by the cosmwasm_storage layout of v1.0.x, only read during migration
 pub static NAME_RESOLVER_KEY: &[u8] = b"nameresolver";
 pub static SUBDOMAIN_MINTED: &[u8] = b"subdomain_minted";
 pub static CONFIG_KEY: &[u8] = b"config";
 
+pub const CONFIG: Item<Config> = Item::new("cfg");
+pub const NAMES: Map<&str, NameRecord> = Map::new("names");
+// (name, key) -> value
+pub const TEXT_RECORDS: Map<(&str, &str), String> = Map::new("texts");
+// (name, chain) -> address, chain is a bech32 prefix or an `eip155:<chain id>` identifier
+pub const CHAIN_ADDRESSES: Map<(&str, &str), String> = Map::new("chain_addresses");
+// (name, key) -> text records of the tenant of a leased name
+pub const LEASE_TEXT_RECORDS: Map<(&str, &str), String> = Map::new("lease_texts");
+// (name, chain) -> chain addresses of the tenant of a leased name
+pub const LEASE_CHAIN_ADDRESSES: Map<(&str, &str), String> = Map::new("lease_chain_addresses");
+// domain -> answer for subdomains of the domain that have no record of their own
+pub const WILDCARDS: Map<&str, Wildcard> = Map::new("wildcards");
+// domain -> terms under which anyone can claim a direct subdomain of it
+pub const REGISTRARS: Map<&str, Registrar> = Map::new("registrars");
+// domain -> terms under which holders of its direct subdomains renew them
+pub const RENEWAL_TERMS: Map<&str, RenewalTerms> = Map::new("renewal_terms");
+// subdomain -> fuses its parent burned, they hold until the subdomain expires
+pub const FUSES: Map<&str, Vec<Fuse>> = Map::new("fuses");
+// (name, manager) -> approval granted by the NFT owner
+pub const MANAGERS: Map<(&str, &Addr), Manager> = Map::new("managers");
+// name -> NFT owner at the time the resolver was last set
+pub const RESOLVER_OWNERS: Map<&str, Addr> = Map::new("resolver_owners");
+// name -> fixed-price listing, only valid while the seller owns the NFT
+pub const LISTINGS: Map<&str, Listing> = Map::new("listings");
+// (name, bidder) -> escrowed offer, kept across re-registrations until withdrawn
+pub const OFFERS: Map<(&str, &Addr), Offer> = Map::new("offers");
+// (bidder, name) -> index of the offers made by every bidder
+pub const BIDDER_OFFERS: Map<(&Addr, &str), Empty> = Map::new("bidder_offers");
+// denom -> total held for open offers, maintained by `save_offer` and `remove_offer`
+pub const ESCROW: Map<&str, Uint128> = Map::new("escrow");
+// name -> lease, the tenant controls the resolver and metadata until it ends
+pub const LEASES: Map<&str, Lease> = Map::new("leases");
+// name -> registration epoch, bumped every time the name is minted
+pub const EPOCHS: Map<&str, u64> = Map::new("epochs");
+// (parent name, label) -> index of the direct subdomains of every name,
+// maintained by `save_record` and `remove_record`
+pub const SUBDOMAINS: Map<(&str, &str), Empty> = Map::new("subdomains");
+// (resolver, name) -> index of the names stored with every resolver, maintained
+// by `save_record` and `remove_record`, a leased name also stays under the
+// owner's resolver until the lease is removed
+pub const ADDRESS_NAMES: Map<(&Addr, &str), Empty> = Map::new("address_names");
+// tld -> settings of the top level domains added next to the default `.arch`
+pub const TLDS: Map<&str, Tld> = Map::new("tlds");
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Config {
     pub admin: Addr,
@@ -18,22 +63,139 @@ pub struct Config {
     pub cw721: Addr,
     pub base_cost: Uint128,
     pub base_expiration: u64,
+    /// share of every public subdomain registration paid to `wallet`
+    #[serde(default)]
+    pub protocol_fee: Decimal,
+    /// share of every marketplace sale paid to `wallet`
+    #[serde(default)]
+    pub royalty: Decimal,
+    /// mirror subdomain changes into the parent's `subdomains` NFT metadata,
+    /// the registry index is kept either way
+    #[serde(default = "default_sync_subdomain_metadata")]
+    pub sync_subdomain_metadata: bool,
+    /// how names resolve once their NFT changed hands
+    #[serde(default)]
+    pub transfer_policy: TransferPolicy,
+    /// naming rules checked when names and subdomains are registered
+    #[serde(default)]
+    pub validation: ValidationRules,
+}
+
+impl Config {
+    /// validate checks the values instantiate, update_config and migrate
+    /// accept
+    pub fn validate(&self) -> StdResult<()> {
+        if self.protocol_fee > Decimal::one() {
+            return Err(StdError::generic_err("protocol_fee cannot exceed 1"));
+        }
+        if self.royalty > Decimal::one() {
+            return Err(StdError::generic_err("royalty cannot exceed 1"));
+        }
+        if !self.validation.is_valid() {
+            return Err(StdError::generic_err("invalid validation rules"));
+        }
+        Ok(())
+    }
+}
+
+pub fn default_sync_subdomain_metadata() -> bool {
+    true
+}
+
+/// What happens to a name's resolver once its NFT is transferred. Transfers
+/// are detected lazily by comparing the NFT owner with the owner that last
+/// set the resolver.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferPolicy {
+    /// keep resolving to the address set by the previous owner
+    Keep,
+    /// resolve to the new NFT owner until it updates the resolver
+    ResetToOwner,
+    /// stop resolving until the new owner updates the resolver
+    MarkStale,
+}
+impl Default for TransferPolicy {
+    fn default() -> Self {
+        TransferPolicy::Keep
+    }
+}
+
+/// Rules a single label must follow, lowercase ascii letters and digits are
+/// always allowed and unicode labels are checked in their punycode form
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct NameRules {
+    pub min_length: u64,
+    pub max_length: u64,
+    /// characters allowed on top of letters and digits, `-` is needed for
+    /// punycode labels
+    pub allowed_chars: String,
+    pub allow_leading_hyphen: bool,
+    pub allow_trailing_hyphen: bool,
+    /// labels matching any of these are refused, `*` matches any run of
+    /// characters, e.g. `admin*`
+    pub reserved: Vec<String>,
+}
+impl NameRules {
+    pub fn allows(&self, c: char) -> bool {
+        c.is_ascii_lowercase() || c.is_ascii_digit() || self.allowed_chars.contains(c)
+    }
+    /// is_valid tells whether the rules themselves make sense
+    pub fn is_valid(&self) -> bool {
+        self.min_length > 0
+            && self.min_length <= self.max_length
+            && self
+                .allowed_chars
+                .chars()
+                .all(|c| c.is_ascii_punctuation() && c != '.')
+    }
+}
+impl Default for NameRules {
+    fn default() -> Self {
+        NameRules {
+            min_length: 3,
+            max_length: 64,
+            allowed_chars: String::from("-_"),
+            allow_leading_hyphen: true,
+            allow_trailing_hyphen: true,
+            reserved: vec![],
+        }
+    }
+}
+
+/// Settings of a top level domain added by the admin, names under `.arch`
+/// follow `Config`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Tld {
+    pub base_cost: Uint128,
+    pub base_expiration: u64,
+    pub validation: ValidationRules,
+    /// collection minting names under this tld, the registry's default
+    /// collection when unset
+    pub cw721: Option<Addr>,
+}
+
+/// Naming rules for top level names and for subdomain labels
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct ValidationRules {
+    pub name: NameRules,
+    pub subdomain: NameRules,
+}
+impl ValidationRules {
+    pub fn is_valid(&self) -> bool {
+        self.name.is_valid() && self.subdomain.is_valid()
+    }
 }
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub enum SubDomainStatus {
     //if subdomain in acive mint domain owner can only extend expiration up to domain expiration
     ExistingMintActive,
     // if subdomain expired owner can remint which will first burn existing nft
-    ExistingMintExpired,    
+    ExistingMintExpired,
     // if new subdomain owner can register and mint / not mint
-    NewSubdomain
-}
-pub fn config(storage: &mut dyn Storage) -> Singleton<Config> {
-    singleton(storage, CONFIG_KEY)
-}
-
-pub fn config_read(storage: &dyn Storage) -> ReadonlySingleton<Config> {
-    singleton_read(storage, CONFIG_KEY)
+    NewSubdomain,
+    // an active resolver-only subdomain is minted in place, keeping its settings
+    ExistingResolverOnly,
 }
 
 /**
@@ -45,16 +207,446 @@ pub struct NameRecord {
     pub resolver: Addr,
     pub created: u64,
     pub expiration: u64,
+    /// when set, resolution is delegated to this contract through `ResolverQueryMsg`
+    #[serde(default)]
+    pub resolver_contract: Option<Addr>,
+    /// epoch of the parent registration a subdomain was created under, the
+    /// subdomain is stale once the parent is registered again
+    #[serde(default)]
+    pub parent_epoch: u64,
+    /// subdomain record without an NFT, managed by the parent owner
+    #[serde(default)]
+    pub resolver_only: bool,
 }
 impl NameRecord {
     pub fn is_expired(&self, block: &BlockInfo) -> bool {
         Expiration::AtTime(Timestamp::from_seconds(self.expiration)).is_expired(block)
     }
 }
-pub fn resolver(storage: &mut dyn Storage) -> Bucket<NameRecord> {
-    bucket(storage, NAME_RESOLVER_KEY)
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Wildcard {
+    pub resolver: Addr,
+    pub resolver_contract: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrarAccess {
+    Open,
+    Allow(Vec<Addr>),
+    Deny(Vec<Addr>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Registrar {
+    pub price: Uint128,
+    pub denom: String,
+    /// seconds a claimed subdomain is registered for, capped by the domain expiration
+    pub duration: u64,
+    pub min_length: u64,
+    pub max_length: u64,
+    pub access: RegistrarAccess,
+}
+impl Registrar {
+    pub fn permits(&self, addr: &Addr) -> bool {
+        match &self.access {
+            RegistrarAccess::Open => true,
+            RegistrarAccess::Allow(list) => list.contains(addr),
+            RegistrarAccess::Deny(list) => !list.contains(addr),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ManagerScope {
+    /// resolver, resolver contract, text records and chain addresses
+    Resolver,
+    /// NFT metadata through `UpdateUserDomainData`
+    Metadata,
+    /// subdomains, wildcards, registrars, renewal terms and fuses
+    Subdomains,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Manager {
+    /// the approval only holds while this address owns the NFT, and is
+    /// dropped for good once the registry sees another owner
+    pub granted_by: Addr,
+    pub scopes: Vec<ManagerScope>,
+    pub expires: Option<u64>,
+}
+impl Manager {
+    pub fn is_active(&self, owner: &str, block: &BlockInfo) -> bool {
+        self.granted_by == owner
+            && self.expires.map_or(true, |expires| {
+                !Expiration::AtTime(Timestamp::from_seconds(expires)).is_expired(block)
+            })
+    }
+}
+
+/// prune_managers drops the approvals of `name` granted by anyone but
+/// `owner`, so they stay revoked if the NFT later returns to their grantor
+pub fn prune_managers(storage: &mut dyn Storage, name: &str, owner: &str) -> StdResult<()> {
+    let approvals = MANAGERS
+        .prefix(name)
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(Addr, Manager)>>>()?;
+    for (manager, approval) in approvals.iter() {
+        if approval.granted_by != owner {
+            MANAGERS.remove(storage, (name, manager));
+        }
+    }
+    Ok(())
+}
+
+/// A name offered for sale at a fixed price, the registry must be an approved
+/// spender of the NFT for a purchase to go through
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Listing {
+    pub seller: Addr,
+    pub price: Uint128,
+    pub denom: String,
+    pub expires: Option<u64>,
+}
+impl Listing {
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        self.expires.map_or(false, |expires| {
+            Expiration::AtTime(Timestamp::from_seconds(expires)).is_expired(block)
+        })
+    }
+}
+
+/// A name lent to `tenant` until `expires`. The lease keeps the owner's
+/// resolver so it can be restored once the lease ends, and it follows the
+/// NFT if it is transferred outside the registry. The tenant's text records
+/// and chain addresses are kept apart from the owner's, which come back
+/// untouched once the lease ends.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Lease {
+    pub tenant: Addr,
+    pub expires: u64,
+    pub resolver: Addr,
+    pub resolver_contract: Option<Addr>,
+}
+impl Lease {
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        Expiration::AtTime(Timestamp::from_seconds(self.expires)).is_expired(block)
+    }
+}
+
+pub fn active_lease(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    name: &str,
+) -> StdResult<Option<Lease>> {
+    Ok(LEASES
+        .may_load(storage, name)?
+        .filter(|lease| !lease.is_expired(block)))
+}
+
+/// return_leased_resolver puts the owner's resolver back on `record` once
+/// the lease of `name` has ended, reads use it until the lease is settled
+pub fn return_leased_resolver(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    name: &str,
+    record: &mut NameRecord,
+) -> StdResult<bool> {
+    match LEASES.may_load(storage, name)? {
+        Some(lease) if lease.is_expired(block) => {
+            record.resolver = lease.resolver;
+            record.resolver_contract = lease.resolver_contract;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// text_records returns the map holding the text records of `name`, the
+/// tenant of an active lease reads and writes its own
+pub fn text_records<'k>(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    name: &str,
+) -> StdResult<Map<'static, (&'k str, &'k str), String>> {
+    Ok(match active_lease(storage, block, name)? {
+        Some(_) => LEASE_TEXT_RECORDS,
+        None => TEXT_RECORDS,
+    })
+}
+
+/// chain_addresses returns the map holding the chain addresses of `name`,
+/// the tenant of an active lease reads and writes its own
+pub fn chain_addresses<'k>(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    name: &str,
+) -> StdResult<Map<'static, (&'k str, &'k str), String>> {
+    Ok(match active_lease(storage, block, name)? {
+        Some(_) => LEASE_CHAIN_ADDRESSES,
+        None => CHAIN_ADDRESSES,
+    })
+}
+
+/// settle_lease writes back the owner's resolver of an ended lease and
+/// removes it along with the records set by the tenant
+pub fn settle_lease(storage: &mut dyn Storage, block: &BlockInfo, name: &str) -> StdResult<()> {
+    if let Some(mut record) = may_load_record(storage, name)? {
+        if return_leased_resolver(storage, block, name, &mut record)? {
+            save_record(storage, name, &record)?;
+            remove_lease(storage, name)?;
+        }
+    }
+    Ok(())
+}
+
+/// remove_lease drops the lease of `name`, the records the tenant set and
+/// the index entry kept for the owner's resolver while the tenant's resolver
+/// was stored on the record
+pub fn remove_lease(storage: &mut dyn Storage, name: &str) -> StdResult<()> {
+    if let Some(lease) = LEASES.may_load(storage, name)? {
+        LEASES.remove(storage, name);
+        clear_lease_records(storage, name)?;
+        let stored = may_load_record(storage, name)?.map(|record| record.resolver);
+        if stored.as_ref() != Some(&lease.resolver) {
+            ADDRESS_NAMES.remove(storage, (&lease.resolver, name));
+        }
+    }
+    Ok(())
+}
+
+/// Funds escrowed by a bidder for a name, the owner can accept the offer
+/// until it expires
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Offer {
+    pub amount: Uint128,
+    pub denom: String,
+    pub expires: u64,
+    /// rejected offers can't be accepted and can be withdrawn right away
+    pub rejected: bool,
+}
+impl Offer {
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        Expiration::AtTime(Timestamp::from_seconds(self.expires)).is_expired(block)
+    }
+}
+
+pub fn save_offer(
+    storage: &mut dyn Storage,
+    name: &str,
+    bidder: &Addr,
+    offer: &Offer,
+) -> StdResult<()> {
+    remove_offer(storage, name, bidder)?;
+    ESCROW.update(storage, &offer.denom, |escrowed| -> StdResult<_> {
+        Ok(escrowed.unwrap_or_default() + offer.amount)
+    })?;
+    OFFERS.save(storage, (name, bidder), offer)?;
+    BIDDER_OFFERS.save(storage, (bidder, name), &Empty {})
+}
+
+pub fn remove_offer(storage: &mut dyn Storage, name: &str, bidder: &Addr) -> StdResult<()> {
+    if let Some(offer) = OFFERS.may_load(storage, (name, bidder))? {
+        ESCROW.update(storage, &offer.denom, |escrowed| -> StdResult<_> {
+            Ok(escrowed.unwrap_or_default().checked_sub(offer.amount)?)
+        })?;
+    }
+    OFFERS.remove(storage, (name, bidder));
+    BIDDER_OFFERS.remove(storage, (bidder, name));
+    Ok(())
+}
+
+/// Rights a parent gives up on one of its subdomains, a burned fuse can't be restored.
+/// Fuses bind the parent only, the holder of a subdomain keeps managing its own record.
+/// `CannotRemove` also protects the subdomain from the removal of any of its ancestors
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Fuse {
+    CannotRemove,
+    CannotShortenExpiry,
+    CannotChangeResolver,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RenewalTerms {
+    pub price: Uint128,
+    pub denom: String,
+    /// seconds added per renewal, capped by the domain expiration
+    pub duration: u64,
+}
+
+/// may_load_record reads from `NAMES`, falling back to the legacy bucket
+/// for records the migration hasn't reached yet
+pub fn may_load_record(storage: &dyn Storage, name: &str) -> StdResult<Option<NameRecord>> {
+    match NAMES.may_load(storage, name)? {
+        Some(record) => Ok(Some(record)),
+        None => bucket_read(storage, NAME_RESOLVER_KEY).may_load(name.as_bytes()),
+    }
+}
+
+/// save_record writes to `NAMES` and drops any legacy copy, so every
+/// write also migrates the record it touches
+pub fn save_record(storage: &mut dyn Storage, name: &str, record: &NameRecord) -> StdResult<()> {
+    if let Some(prev) = may_load_record(storage, name)? {
+        let leased_from = LEASES
+            .may_load(storage, name)?
+            .map_or(false, |lease| lease.resolver == prev.resolver);
+        if prev.resolver != record.resolver && !leased_from {
+            ADDRESS_NAMES.remove(storage, (&prev.resolver, name));
+        }
+    }
+    NAMES.save(storage, name, record)?;
+    bucket::<NameRecord>(storage, NAME_RESOLVER_KEY).remove(name.as_bytes());
+    ADDRESS_NAMES.save(storage, (&record.resolver, name), &Empty {})?;
+    if let Some((label, parent)) = split_subdomain(name) {
+        SUBDOMAINS.save(storage, (parent, label), &Empty {})?;
+    }
+    Ok(())
+}
+
+pub fn remove_record(storage: &mut dyn Storage, name: &str) -> StdResult<()> {
+    if let Some(record) = may_load_record(storage, name)? {
+        ADDRESS_NAMES.remove(storage, (&record.resolver, name));
+    }
+    if let Some(lease) = LEASES.may_load(storage, name)? {
+        ADDRESS_NAMES.remove(storage, (&lease.resolver, name));
+    }
+    NAMES.remove(storage, name);
+    bucket::<NameRecord>(storage, NAME_RESOLVER_KEY).remove(name.as_bytes());
+    if let Some((label, parent)) = split_subdomain(name) {
+        SUBDOMAINS.remove(storage, (parent, label));
+    }
+    Ok(())
+}
+
+// "a.b.arch" -> ("a", "b.arch"), top level names have no parent
+fn split_subdomain(name: &str) -> Option<(&str, &str)> {
+    match name.split_once('.') {
+        Some((label, parent)) if parent.contains('.') => Some((label, parent)),
+        _ => None,
+    }
+}
+
+/// subdomain_labels lists the labels of the direct subdomains of `name`
+pub fn subdomain_labels(storage: &dyn Storage, name: &str) -> StdResult<Vec<String>> {
+    SUBDOMAINS
+        .prefix(name)
+        .keys(storage, None, None, Order::Ascending)
+        .collect()
+}
+
+pub fn clear_text_records(storage: &mut dyn Storage, name: &str) -> StdResult<()> {
+    let keys = TEXT_RECORDS
+        .prefix(name)
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<String>>>()?;
+    for key in keys.iter() {
+        TEXT_RECORDS.remove(storage, (name, key.as_str()));
+    }
+    Ok(())
+}
+
+pub fn clear_chain_addresses(storage: &mut dyn Storage, name: &str) -> StdResult<()> {
+    let chains = CHAIN_ADDRESSES
+        .prefix(name)
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<String>>>()?;
+    for chain in chains.iter() {
+        CHAIN_ADDRESSES.remove(storage, (name, chain.as_str()));
+    }
+    Ok(())
+}
+
+fn clear_lease_records(storage: &mut dyn Storage, name: &str) -> StdResult<()> {
+    let keys = LEASE_TEXT_RECORDS
+        .prefix(name)
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<String>>>()?;
+    for key in keys.iter() {
+        LEASE_TEXT_RECORDS.remove(storage, (name, key.as_str()));
+    }
+    let chains = LEASE_CHAIN_ADDRESSES
+        .prefix(name)
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<String>>>()?;
+    for chain in chains.iter() {
+        LEASE_CHAIN_ADDRESSES.remove(storage, (name, chain.as_str()));
+    }
+    Ok(())
+}
+
+pub fn current_epoch(storage: &dyn Storage, name: &str) -> StdResult<u64> {
+    Ok(EPOCHS.may_load(storage, name)?.unwrap_or_default())
+}
+
+/// next_epoch starts a new registration epoch for `name`, invalidating
+/// every subdomain created under the previous one
+pub fn next_epoch(storage: &mut dyn Storage, name: &str) -> StdResult<u64> {
+    let epoch = current_epoch(storage, name)? + 1;
+    EPOCHS.save(storage, name, &epoch)?;
+    Ok(epoch)
+}
+
+pub fn has_fuse(storage: &dyn Storage, name: &str, fuse: Fuse) -> StdResult<bool> {
+    Ok(FUSES
+        .may_load(storage, name)?
+        .map_or(false, |fuses| fuses.contains(&fuse)))
+}
+
+/// clear_name_settings drops everything the owner configured on a name,
+/// used when the name is burned or changes hands through re-registration
+pub fn clear_name_settings(storage: &mut dyn Storage, name: &str) -> StdResult<()> {
+    clear_text_records(storage, name)?;
+    clear_chain_addresses(storage, name)?;
+    WILDCARDS.remove(storage, name);
+    REGISTRARS.remove(storage, name);
+    RENEWAL_TERMS.remove(storage, name);
+    FUSES.remove(storage, name);
+    RESOLVER_OWNERS.remove(storage, name);
+    LISTINGS.remove(storage, name);
+    remove_lease(storage, name)?;
+    let managers = MANAGERS
+        .prefix(name)
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<Addr>>>()?;
+    for manager in managers.iter() {
+        MANAGERS.remove(storage, (name, manager));
+    }
+    Ok(())
+}
+
+/// migrate_legacy_config moves the v1.0.x singleton config into `CONFIG`
+pub fn migrate_legacy_config(storage: &mut dyn Storage) -> StdResult<()> {
+    let legacy: Option<Config> = singleton_read(storage, CONFIG_KEY).may_load()?;
+    if let Some(c) = legacy {
+        CONFIG.save(storage, &c)?;
+        singleton::<Config>(storage, CONFIG_KEY).remove();
+    }
+    Ok(())
+}
+
+/// migrate_legacy_records moves up to `limit` records out of the legacy
+/// bucket and returns the names it moved. Migrated entries are removed
+/// from the bucket, so calling it again resumes where it stopped.
+pub fn migrate_legacy_records(storage: &mut dyn Storage, limit: usize) -> StdResult<Vec<String>> {
+    let batch = bucket_read::<NameRecord>(storage, NAME_RESOLVER_KEY)
+        .range(None, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<(Vec<u8>, NameRecord)>>>()?;
+    let mut migrated = vec![];
+    for (key, record) in batch.into_iter() {
+        let name = String::from_utf8(key)?;
+        save_record(storage, &name, &record)?;
+        migrated.push(name);
+    }
+    Ok(migrated)
 }
 
-pub fn resolver_read(storage: &dyn Storage) -> ReadonlyBucket<NameRecord> {
-    bucket_read(storage, NAME_RESOLVER_KEY)
+pub fn has_legacy_records(storage: &dyn Storage) -> bool {
+    bucket_read::<NameRecord>(storage, NAME_RESOLVER_KEY)
+        .range(None, None, Order::Ascending)
+        .next()
+        .is_some()
 }
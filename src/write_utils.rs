@@ -1,12 +1,18 @@
 use cosmwasm_std::{
-    to_binary, Addr, BankMsg, Coin, CosmosMsg, DepsMut, StdResult,
+    to_binary, Addr, BankMsg, Coin, CosmosMsg, Decimal, DepsMut, Order, StdResult, Storage,
     Uint128, WasmMsg,Env
 };
 
 
 use crate::read_utils::get_name_body;
-use crate::read_utils::{ query_current_metadata};
-use crate::state::{ resolver, NameRecord};
+use crate::read_utils::{
+    display_name, get_subdomain_prefix, is_stale, query_current_metadata,
+};
+use crate::state::{
+    clear_chain_addresses, clear_name_settings, clear_text_records, current_epoch,
+    may_load_record, next_epoch, remove_lease, remove_record, save_record, subdomain_labels,
+    NameRecord, CONFIG, LISTINGS, MANAGERS, RESOLVER_OWNERS,
+};
 use archid_token::{
     ExecuteMsg as Cw721ExecuteMsg, Metadata, MintMsg, Subdomain, UpdateMetadataMsg,
 };
@@ -23,8 +29,8 @@ pub fn add_subdomain_metadata(
     created: u64,
     expiry: u64,
 ) -> StdResult<CosmosMsg> {
-    let mut current_metadata: Metadata = query_current_metadata(&name, cw721, deps).unwrap();
-    let mut subdomains: Vec<Subdomain> = current_metadata.subdomains.as_ref().unwrap().clone();
+    let mut current_metadata: Metadata = query_current_metadata(&name, cw721, deps)?;
+    let mut subdomains: Vec<Subdomain> = current_metadata.subdomains.clone().unwrap_or_default();
     subdomains.push(Subdomain {
         name: Some(subdomain),
         resolver: Some(resolver),
@@ -45,15 +51,26 @@ pub fn update_subdomain_metadata(
     resolver: Addr,
     expiry: u64,
 ) -> StdResult<CosmosMsg> {
-    let mut current_metadata: Metadata = query_current_metadata(domain, cw721, deps).unwrap();
-    let mut subdomains: Vec<Subdomain> = current_metadata.subdomains.as_ref().unwrap().clone();
-    let index = subdomains
+    let mut current_metadata: Metadata = query_current_metadata(domain, cw721, deps)?;
+    let mut subdomains: Vec<Subdomain> = current_metadata.subdomains.clone().unwrap_or_default();
+    // entries can be missing when syncing was off while the subdomain was created
+    match subdomains
         .iter()
-        .position(|r| &r.clone().name.unwrap() == subdomain)
-        .unwrap();
-    subdomains[index].expiry = Some(expiry);
-    subdomains[index].minted = None;
-    subdomains[index].resolver = Some(resolver);
+        .position(|r| r.name.as_ref() == Some(subdomain))
+    {
+        Some(index) => {
+            subdomains[index].expiry = Some(expiry);
+            subdomains[index].minted = None;
+            subdomains[index].resolver = Some(resolver);
+        }
+        None => subdomains.push(Subdomain {
+            name: Some(subdomain.clone()),
+            resolver: Some(resolver),
+            minted: None,
+            created: None,
+            expiry: Some(expiry),
+        }),
+    }
     current_metadata.subdomains = Some((*subdomains).to_vec());
     let resp = send_data_update(domain, cw721, current_metadata)?;
     Ok(resp)
@@ -67,23 +84,27 @@ pub fn update_subdomain_expiry(
 ) -> StdResult<Vec<CosmosMsg>> {
     let mut messages = Vec::new();
     let domain_route: String = format!("{}.{}", subdomain, domain);
-    let key = domain_route.as_bytes();
-    let domain_config: NameRecord = (resolver(deps.storage).may_load(key)?).unwrap();
+    let domain_config: NameRecord = (may_load_record(deps.storage, &domain_route)?).unwrap();
     let record = NameRecord {
         resolver: domain_config.resolver.clone(),
         created: domain_config.created,
         expiration,
+        resolver_contract: domain_config.resolver_contract.clone(),
+        parent_epoch: domain_config.parent_epoch,
+        resolver_only: domain_config.resolver_only,
     };
-    resolver(deps.storage).save(key, &record)?;
-    let msg = update_subdomain_metadata(
-        &deps,
-        &nft,
-        &domain,
-        &subdomain,
-        domain_config.resolver,
-        expiration,
-    )?;
-    messages.push(msg);
+    save_record(deps.storage, &domain_route, &record)?;
+    if !domain_config.resolver_only && sync_subdomain_metadata(deps.storage)? {
+        let msg = update_subdomain_metadata(
+            &deps,
+            &nft,
+            &domain,
+            &subdomain,
+            domain_config.resolver,
+            expiration,
+        )?;
+        messages.push(msg);
+    }
 
     Ok(messages)
 }
@@ -93,15 +114,55 @@ pub fn remove_subdomain_metadata(
     name: String,
     subdomain: String,
 ) -> StdResult<CosmosMsg> {
-    let mut current_metadata: Metadata = query_current_metadata(&name, cw721, deps).unwrap();
-    let mut subdomains = current_metadata.subdomains.as_ref().unwrap().clone();
+    let mut current_metadata: Metadata = query_current_metadata(&name, cw721, deps)?;
+    let mut subdomains = current_metadata.subdomains.clone().unwrap_or_default();
 
-    subdomains.retain(|item| item.name.as_ref().unwrap().as_bytes() != subdomain.as_bytes());
+    subdomains.retain(|item| item.name.as_ref() != Some(&subdomain));
     current_metadata.subdomains = Some((*subdomains).to_vec());
     let resp = send_data_update(&name, cw721, current_metadata)?;
     Ok(resp)
 }
 
+/// subdomain_descendants walks the subdomain index below `name` and
+/// returns every descendant, at any depth
+pub fn subdomain_descendants(storage: &dyn Storage, name: &str) -> StdResult<Vec<String>> {
+    let mut descendants: Vec<String> = vec![];
+    let mut pending = vec![name.to_string()];
+    while let Some(parent) = pending.pop() {
+        for label in subdomain_labels(storage, &parent)? {
+            let child = format!("{}.{}", label, parent);
+            descendants.push(child.clone());
+            pending.push(child);
+        }
+    }
+    Ok(descendants)
+}
+
+/// remove_names deletes the records and settings of `names` and burns the
+/// NFTs of the minted ones, resolver-only records have no token to burn
+pub fn remove_names(
+    storage: &mut dyn Storage,
+    names: &[String],
+    cw721: &Addr,
+) -> StdResult<Vec<CosmosMsg>> {
+    let mut messages = vec![];
+    for name in names.iter() {
+        let minted = may_load_record(storage, name)?.map_or(false, |r| !r.resolver_only);
+        remove_record(storage, name)?;
+        clear_name_settings(storage, name)?;
+        if minted {
+            messages.push(burn_handler(name, cw721)?);
+        }
+    }
+    Ok(messages)
+}
+
+/// sync_subdomain_metadata tells whether subdomain changes are mirrored
+/// into the parent's NFT metadata
+pub fn sync_subdomain_metadata(storage: &dyn Storage) -> StdResult<bool> {
+    Ok(CONFIG.load(storage)?.sync_subdomain_metadata)
+}
+
 pub fn mint_handler(
     name: &String,
     creator: &Addr,
@@ -133,7 +194,7 @@ pub fn mint_handler(
 
     let mint_extension = Some(Metadata {
         description: Some(description),
-        name: Some(body),
+        name: Some(display_name(&body)),
         image: None,
         created: Some(created),
         expiry: Some(expiration),
@@ -172,11 +233,73 @@ pub fn burn_handler(name: &String, cw721: &Addr) -> StdResult<CosmosMsg> {
     Ok(resp)
 }
 
+// the registry moves the token as a spender, so the owner must have approved it
+pub fn transfer_handler(name: &str, recipient: &Addr, cw721: &Addr) -> StdResult<CosmosMsg> {
+    let transfer_msg: Cw721ExecuteMsg = Cw721ExecuteMsg::TransferNft {
+        recipient: recipient.to_string(),
+        token_id: name.to_string(),
+    };
+    let resp: CosmosMsg = WasmMsg::Execute {
+        contract_addr: cw721.to_string(),
+        msg: to_binary(&transfer_msg)?,
+        funds: vec![],
+    }
+    .into();
+    Ok(resp)
+}
+
+/// hand_over_name moves the NFT of `name` to `recipient` and points the name
+/// at `resolver`. Text records, chain addresses, managers and the listing of
+/// the previous owner don't carry over, subdomains stay valid as the registration epoch
+/// is unchanged.
+pub fn hand_over_name(
+    deps: DepsMut,
+    nft: &Addr,
+    name: &str,
+    mut record: NameRecord,
+    recipient: &Addr,
+    resolver: Addr,
+) -> StdResult<Vec<CosmosMsg>> {
+    let mut messages = vec![transfer_handler(name, recipient, nft)?];
+    if let Some(s) = get_subdomain_prefix(name.to_string()) {
+        if sync_subdomain_metadata(deps.storage)? {
+            messages.push(update_subdomain_metadata(
+                &deps,
+                nft,
+                &s[1],
+                &s[0],
+                resolver.clone(),
+                record.expiration,
+            )?);
+        }
+    }
+    record.resolver = resolver;
+    record.resolver_contract = None;
+    save_record(deps.storage, name, &record)?;
+    RESOLVER_OWNERS.save(deps.storage, name, recipient)?;
+    LISTINGS.remove(deps.storage, name);
+    remove_lease(deps.storage, name)?;
+    clear_text_records(deps.storage, name)?;
+    clear_chain_addresses(deps.storage, name)?;
+    let managers = MANAGERS
+        .prefix(name)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<Addr>>>()?;
+    for manager in managers.iter() {
+        MANAGERS.remove(deps.storage, (name, manager));
+    }
+    Ok(messages)
+}
+
 pub fn send_tokens(to: &Addr, amount: Uint128) -> StdResult<CosmosMsg> {
+    send_coin(to, amount, DENOM)
+}
+
+pub fn send_coin(to: &Addr, amount: Uint128, denom: &str) -> StdResult<CosmosMsg> {
     let msg = BankMsg::Send {
         to_address: to.into(),
         amount: ([Coin {
-            denom: String::from(DENOM),
+            denom: String::from(denom),
             amount,
         }])
         .to_vec(),
@@ -184,6 +307,27 @@ pub fn send_tokens(to: &Addr, amount: Uint128) -> StdResult<CosmosMsg> {
     Ok(msg.into())
 }
 
+/// split_payment pays `amount` to `owner`, minus the `protocol_fee` share
+/// which goes to `wallet`
+pub fn split_payment(
+    owner: &Addr,
+    wallet: &Addr,
+    amount: Uint128,
+    denom: &str,
+    protocol_fee: Decimal,
+) -> StdResult<Vec<CosmosMsg>> {
+    let mut messages = Vec::new();
+    let protocol_share = amount * protocol_fee;
+    let owner_share = amount - protocol_share;
+    if !owner_share.is_zero() {
+        messages.push(send_coin(owner, owner_share, denom)?);
+    }
+    if !protocol_share.is_zero() {
+        messages.push(send_coin(wallet, protocol_share, denom)?);
+    }
+    Ok(messages)
+}
+
 pub fn send_data_update(name: &String, cw721: &Addr, data: Metadata) -> StdResult<CosmosMsg> {
     let update = Cw721ExecuteMsg::UpdateMetadata(UpdateMetadataMsg {
         token_id: name.to_string(),
@@ -221,26 +365,33 @@ pub fn register_new_subdomain(
     expiration: u64,
 ) -> StdResult<Vec<CosmosMsg>> {
     let domain_route: String = format!("{}.{}", subdomain, domain);
-    let key = domain_route.as_bytes();
     let mut messages = Vec::new();
     let created = env.block.time.seconds();
+    let parent_epoch = current_epoch(deps.storage, &domain)?;
+    next_epoch(deps.storage, &domain_route)?;
 
-    let metadata_msg = add_subdomain_metadata(
-        &deps,
-        &nft,
-        domain,
-        subdomain,
-        new_resolver.clone(),
-        env.block.time.seconds(),
-        expiration,
-    )?;
-    messages.push(metadata_msg);
+    if sync_subdomain_metadata(deps.storage)? {
+        let metadata_msg = add_subdomain_metadata(
+            &deps,
+            &nft,
+            domain,
+            subdomain,
+            new_resolver.clone(),
+            env.block.time.seconds(),
+            expiration,
+        )?;
+        messages.push(metadata_msg);
+    }
     let record = NameRecord {
         resolver: new_resolver,
         created,
         expiration,
+        resolver_contract: None,
+        parent_epoch,
+        resolver_only: false,
     };
-    resolver(deps.storage).save(key, &record)?;
+    save_record(deps.storage, &domain_route, &record)?;
+    RESOLVER_OWNERS.save(deps.storage, &domain_route, &new_owner)?;
 
     let resp = mint_handler(&domain_route, &new_owner, &nft, created, expiration)?;
     messages.push(resp);
@@ -261,31 +412,134 @@ pub fn burn_remint_subdomain(
     expiration: u64,
 ) -> StdResult<Vec<CosmosMsg>> {
     let domain_route: String = format!("{}.{}", subdomain, domain);
-    let key = domain_route.as_bytes();
     let mut messages = Vec::new();
     let created = env.block.time.seconds();
-    let burn_msg = burn_handler(&format!("{}.{}", subdomain, domain), &nft)?;
-    messages.push(burn_msg);
+    let parent_epoch = current_epoch(deps.storage, &domain)?;
+    let minted = may_load_record(deps.storage, &domain_route)?.map_or(true, |r| !r.resolver_only);
+    // the previous holder's settings and subdomains don't carry over
+    next_epoch(deps.storage, &domain_route)?;
+    clear_name_settings(deps.storage, &domain_route)?;
+    if minted {
+        let burn_msg = burn_handler(&format!("{}.{}", subdomain, domain), &nft)?;
+        messages.push(burn_msg);
+    }
 
-    let metadata_msg = add_subdomain_metadata(
-        &deps,
-        &nft,
-        domain,
-        subdomain,
-        new_resolver.clone(),
-        env.block.time.seconds(),
-        expiration,
-    )?;
-    messages.push(metadata_msg);
+    if sync_subdomain_metadata(deps.storage)? {
+        let metadata_msg = add_subdomain_metadata(
+            &deps,
+            &nft,
+            domain,
+            subdomain,
+            new_resolver.clone(),
+            env.block.time.seconds(),
+            expiration,
+        )?;
+        messages.push(metadata_msg);
+    }
     let record = NameRecord {
         resolver: new_resolver,
         created,
         expiration,
+        resolver_contract: None,
+        parent_epoch,
+        resolver_only: false,
     };
-    resolver(deps.storage).save(key, &record)?;
+    save_record(deps.storage, &domain_route, &record)?;
+    RESOLVER_OWNERS.save(deps.storage, &domain_route, &new_owner)?;
 
     let resp = mint_handler(&domain_route, &new_owner, &nft, created, expiration)?;
     messages.push(resp);
 
     Ok(messages)
-}
\ No newline at end of file
+}
+/// mint_resolver_only_subdomain turns an active resolver-only record into a
+/// minted subdomain, keeping its settings and registration dates
+pub fn mint_resolver_only_subdomain(
+    deps: DepsMut,
+    nft: Addr,
+    domain: String,
+    subdomain: String,
+    new_resolver: Addr,
+    new_owner: Addr,
+) -> StdResult<Vec<CosmosMsg>> {
+    let domain_route: String = format!("{}.{}", subdomain, domain);
+    let mut messages = Vec::new();
+    let mut record = (may_load_record(deps.storage, &domain_route)?).unwrap();
+    record.resolver = new_resolver.clone();
+    record.resolver_only = false;
+    save_record(deps.storage, &domain_route, &record)?;
+    RESOLVER_OWNERS.save(deps.storage, &domain_route, &new_owner)?;
+
+    if sync_subdomain_metadata(deps.storage)? {
+        let metadata_msg = add_subdomain_metadata(
+            &deps,
+            &nft,
+            domain,
+            subdomain,
+            new_resolver,
+            record.created,
+            record.expiration,
+        )?;
+        messages.push(metadata_msg);
+    }
+    let resp = mint_handler(
+        &domain_route,
+        &new_owner,
+        &nft,
+        record.created,
+        record.expiration,
+    )?;
+    messages.push(resp);
+
+    Ok(messages)
+}
+
+/// set_resolver_only_subdomain writes a subdomain record that only resolves,
+/// no NFT is minted and the parent's metadata is left alone. Expired or
+/// stale records of the name are replaced, burning their NFT if any.
+pub fn set_resolver_only_subdomain(
+    deps: DepsMut,
+    nft: Addr,
+    env: Env,
+    domain: String,
+    subdomain: String,
+    resolver: Addr,
+    expiration: u64,
+) -> StdResult<Vec<CosmosMsg>> {
+    let domain_route: String = format!("{}.{}", subdomain, domain);
+    let mut messages = Vec::new();
+    let parent_epoch = current_epoch(deps.storage, &domain)?;
+    let created = match may_load_record(deps.storage, &domain_route)? {
+        // an active resolver-only record is updated in place
+        Some(r)
+            if r.resolver_only
+                && !r.is_expired(&env.block)
+                && !is_stale(deps.storage, &domain_route, &r)? =>
+        {
+            r.created
+        }
+        Some(r) => {
+            next_epoch(deps.storage, &domain_route)?;
+            clear_name_settings(deps.storage, &domain_route)?;
+            if !r.resolver_only {
+                messages.push(burn_handler(&domain_route, &nft)?);
+            }
+            env.block.time.seconds()
+        }
+        None => {
+            next_epoch(deps.storage, &domain_route)?;
+            env.block.time.seconds()
+        }
+    };
+    let record = NameRecord {
+        resolver,
+        created,
+        expiration,
+        resolver_contract: None,
+        parent_epoch,
+        resolver_only: true,
+    };
+    save_record(deps.storage, &domain_route, &record)?;
+
+    Ok(messages)
+}